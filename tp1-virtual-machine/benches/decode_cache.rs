@@ -0,0 +1,108 @@
+//! Instructions per second of long running programs: stepping and decoding
+//! every instruction at every step, stepping with the decode cache, and
+//! running with the decode cache, which also runs fused sequences. Each
+//! program runs on a bare machine, then with the devices attached by the
+//! command line, as `+dev`.
+//!
+//! Run with `cargo bench --bench decode_cache`.
+
+use interpreter::{
+    Console, Framebuffer, Machine, Timer, CONSOLE_BASE, FRAMEBUFFER_BASE, TIMER_BASE,
+};
+use std::hint::black_box;
+use std::io;
+use std::time::Instant;
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+use common::Args;
+
+// Name and image of the programs, with their arguments
+const PROGRAMS: [(&str, &[u8], Args<'static>); 2] = [
+    ("fibo(22)", include_bytes!("../tests/fibo.bin"), &[(10, 22)]),
+    (
+        "multiply",
+        include_bytes!("../tests/multiply.bin"),
+        &[(1, 3), (11, 7), (12, 200_000)],
+    ),
+];
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Decoding,
+    Cached,
+    Fused,
+}
+
+// Run `image` to completion in `mode`, with the devices of the command
+// line if `devices`, returning the number of seconds it took.
+fn measure(image: &[u8], args: Args, mode: Mode, devices: bool) -> f64 {
+    let mut machine = Machine::new(image);
+    if devices {
+        machine.attach(CONSOLE_BASE, Box::new(Console::new(io::sink())));
+        machine.attach(TIMER_BASE, Box::new(Timer::new()));
+        machine.attach(FRAMEBUFFER_BASE, Box::new(Framebuffer::new(io::sink())));
+    }
+    machine.set_decode_cache(!matches!(mode, Mode::Decoding));
+    for &(reg, value) in args {
+        machine.set_reg(reg, value).unwrap();
+    }
+    let mut output = Vec::new();
+    let start = Instant::now();
+    match mode {
+        Mode::Fused => machine.run_on(&mut output).unwrap(),
+        _ => while !machine.step_on(&mut output).unwrap() {},
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+    black_box(machine.regs());
+    elapsed
+}
+
+// Number of instructions executed by `image`.
+fn steps(image: &[u8], args: Args) -> u64 {
+    let mut machine = Machine::new(image);
+    for &(reg, value) in args {
+        machine.set_reg(reg, value).unwrap();
+    }
+    let mut steps = 1;
+    while !machine.step_on(&mut Vec::new()).unwrap() {
+        steps += 1;
+    }
+    steps
+}
+
+fn main() {
+    println!(
+        "{:<14} {:>10} {:>14} {:>14} {:>14} {:>8}",
+        "program", "steps", "decoding/s", "cached/s", "fused/s", "speedup"
+    );
+    for (name, image, args) in PROGRAMS {
+        let steps = steps(image, args);
+        for devices in [false, true] {
+            // Keep the best of a few runs
+            let rate = |mode| {
+                let best = (0..5)
+                    .map(|_| measure(image, args, mode, devices))
+                    .fold(f64::INFINITY, f64::min);
+                steps as f64 / best
+            };
+            let before = rate(Mode::Decoding);
+            let cached = rate(Mode::Cached);
+            let fused = rate(Mode::Fused);
+            let name = if devices {
+                format!("{}+dev", name)
+            } else {
+                name.to_string()
+            };
+            println!(
+                "{:<14} {:>10} {:>14.0} {:>14.0} {:>14.0} {:>7.2}x",
+                name,
+                steps,
+                before,
+                cached,
+                fused,
+                fused / before
+            );
+        }
+    }
+}
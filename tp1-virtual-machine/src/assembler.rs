@@ -0,0 +1,526 @@
+use crate::{Instruction, CONTROL_REGISTERS};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Result of a successful assembly: the memory image to give to
+/// [Machine::new](crate::Machine::new) and the labels found in the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Program {
+    /// Bytes of the image, starting at address 0.
+    pub bytes: Vec<u8>,
+    /// Labels in order of definition, with their address.
+    pub labels: Vec<(String, u32)>,
+}
+
+impl Program {
+    /// Address of the label called `name`, if it is defined.
+    pub fn label_address(&self, name: &str) -> Option<u32> {
+        self.labels
+            .iter()
+            .find(|(label, _)| label == name)
+            .map(|&(_, address)| address)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AssemblerError {
+    // A line could not be parsed.
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    // A label is defined more than once.
+    DuplicateLabel {
+        line: usize,
+        label: String,
+    },
+    // An immediate refers to a label which is never defined.
+    UndefinedLabel {
+        line: usize,
+        label: String,
+    },
+    // An immediate does not fit in 16 signed bits.
+    ImmediateOutOfRange {
+        line: usize,
+        value: i64,
+    },
+    // The address column of a listing line does not match the real address.
+    AddressMismatch {
+        line: usize,
+        expected: u32,
+        found: u32,
+    },
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssemblerError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            AssemblerError::DuplicateLabel { line, label } => {
+                write!(f, "line {}: label `{}` is already defined", line, label)
+            }
+            AssemblerError::UndefinedLabel { line, label } => {
+                write!(f, "line {}: undefined label `{}`", line, label)
+            }
+            AssemblerError::ImmediateOutOfRange { line, value } => {
+                write!(
+                    f,
+                    "line {}: immediate {} does not fit in 16 bits",
+                    line, value
+                )
+            }
+            AssemblerError::AddressMismatch {
+                line,
+                expected,
+                found,
+            } => write!(
+                f,
+                "line {}: listing says address {:04} but the instruction is at {:04}",
+                line, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AssemblerError {}
+
+enum Statement {
+    Instruction(Instruction),
+    // An instruction whose immediate is the address of a label, resolved
+    // once every label is known.
+    WithLabel(Instruction, String),
+    Data(Vec<u8>),
+}
+
+impl Statement {
+    fn size(&self) -> u32 {
+        match self {
+            Statement::Instruction(instruction) => instruction.size() as u32,
+            Statement::WithLabel(instruction, _) => instruction.size() as u32,
+            Statement::Data(bytes) => bytes.len() as u32,
+        }
+    }
+}
+
+/// Assemble a program written in the syntax of the `.dis` listings.
+///
+/// Each line holds at most one label definition (`name:`) or one
+/// statement, optionally preceded by the address column of a listing
+/// (`0012` or `????`). Statements are either instructions, written as
+/// the disassembler prints them (`loadimm r3 <- #label`,
+/// `move r0 <- r9 if r8 != 0`, …), or data given as a bytes literal
+/// (`b'Hello\n'`) or a list of bytes (`[0, 0, 0, 0]`). Everything after
+/// a `;` outside of a literal is a comment.
+///
+/// The immediate of a jump or a branch is an offset from the next
+/// instruction, and a label given there is converted into such an offset.
+///
+/// When an address column is present, it must match the address at which
+/// the statement is assembled.
+pub fn assemble(source: &str) -> Result<Program, AssemblerError> {
+    let mut labels: Vec<(String, u32)> = Vec::new();
+    let mut statements = Vec::new();
+    let mut address = 0u32;
+
+    // First pass: parse everything and assign addresses to labels.
+    for (index, raw) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = strip_comment(raw).trim();
+        if text.is_empty() {
+            continue;
+        }
+        if let Some(label) = text.strip_suffix(':') {
+            if !is_identifier(label) {
+                return Err(syntax(line, format!("invalid label `{}`", label)));
+            }
+            if labels.iter().any(|(l, _)| l == label) {
+                return Err(AssemblerError::DuplicateLabel {
+                    line,
+                    label: label.to_string(),
+                });
+            }
+            labels.push((label.to_string(), address));
+            continue;
+        }
+        let (column, rest) = split_address_column(text);
+        if let Some(found) = column {
+            if found != address {
+                return Err(AssemblerError::AddressMismatch {
+                    line,
+                    expected: address,
+                    found,
+                });
+            }
+        }
+        let statement = parse_statement(line, rest)?;
+        address += statement.size();
+        statements.push((line, statement));
+    }
+
+    // Second pass: resolve labels and emit the bytes.
+    let symbols: HashMap<&str, u32> = labels.iter().map(|(l, a)| (l.as_str(), *a)).collect();
+    let mut bytes = Vec::with_capacity(address as usize);
+    for (line, statement) in statements {
+        match statement {
+            Statement::Instruction(instruction) => bytes.extend(instruction.encode()),
+            Statement::WithLabel(instruction, label) => {
+                let Some(&address) = symbols.get(label.as_str()) else {
+                    return Err(AssemblerError::UndefinedLabel { line, label });
+                };
+                // Jumps and branches are relative to the next instruction
+                let value = match instruction {
+                    Instruction::Jump(_)
+                    | Instruction::BranchZero(..)
+                    | Instruction::BranchNonZero(..) => {
+                        address as i64 - (bytes.len() + instruction.size()) as i64
+                    }
+                    _ => address as i64,
+                };
+                let value = i16::try_from(value)
+                    .map_err(|_| AssemblerError::ImmediateOutOfRange { line, value })?;
+                bytes.extend(with_immediate(instruction, value).encode());
+            }
+            Statement::Data(data) => bytes.extend_from_slice(&data),
+        }
+    }
+
+    Ok(Program { bytes, labels })
+}
+
+fn syntax(line: usize, message: String) -> AssemblerError {
+    AssemblerError::Syntax { line, message }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        _ => false,
+    }
+}
+
+// Remove a `;` comment, ignoring semicolons inside quoted literals.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => (),
+            None if c == '\'' || c == '"' => quote = Some(c),
+            None if c == ';' => return &line[..i],
+            None => (),
+        }
+    }
+    line
+}
+
+// Split the optional `0012` or `????` column found in listings.
+fn split_address_column(text: &str) -> (Option<u32>, &str) {
+    let (first, rest) = match text.split_once(char::is_whitespace) {
+        Some((first, rest)) => (first, rest.trim_start()),
+        None => (text, ""),
+    };
+    if first == "????" {
+        (None, rest)
+    } else if !first.is_empty() && first.bytes().all(|b| b.is_ascii_digit()) {
+        (first.parse().ok(), rest)
+    } else {
+        (None, text)
+    }
+}
+
+fn parse_statement(line: usize, text: &str) -> Result<Statement, AssemblerError> {
+    if text.starts_with("b'") || text.starts_with("b\"") {
+        return parse_bytes_literal(line, &text[1..]).map(Statement::Data);
+    }
+    if text.starts_with('[') {
+        return parse_byte_list(line, text).map(Statement::Data);
+    }
+    let (mnemonic, operands) = match text.split_once(char::is_whitespace) {
+        Some((m, o)) => (m, o.trim()),
+        None => (text, ""),
+    };
+    let tokens = tokenize(operands);
+    let tokens: Vec<&str> = tokens.iter().map(String::as_str).collect();
+    let reg = |token: &str| parse_register(line, token);
+    let control = |token: &str| parse_control_register(line, token);
+    let invalid_operands = || {
+        syntax(
+            line,
+            format!("invalid operands for `{}`: `{}`", mnemonic, operands),
+        )
+    };
+    if let Some(operation) = binary_operation(mnemonic) {
+        return match &tokens[..] {
+            [a, "<-", b, ",", c] => {
+                Ok(Statement::Instruction(operation(reg(a)?, reg(b)?, reg(c)?)))
+            }
+            _ => Err(invalid_operands()),
+        };
+    }
+    let instruction = match (mnemonic, &tokens[..]) {
+        ("move", [a, "<-", b, "if", c, "!=", "0"]) => {
+            Instruction::MoveIf(reg(a)?, reg(b)?, reg(c)?)
+        }
+        ("store", ["[", a, "]", "<-", b]) => Instruction::Store(reg(a)?, reg(b)?),
+        ("load", [a, "<-", "[", b, "]"]) => Instruction::Load(reg(a)?, reg(b)?),
+        ("loadimm", [a, "<-", imm]) => match parse_immediate(line, imm)? {
+            Imm::Value(value) => Instruction::LoadImm(reg(a)?, value),
+            Imm::Label(label) => {
+                return Ok(Statement::WithLabel(
+                    Instruction::LoadImm(reg(a)?, 0),
+                    label,
+                ))
+            }
+        },
+        ("sub", [a, "<-", b, "-", c]) => Instruction::Sub(reg(a)?, reg(b)?, reg(c)?),
+        ("out", [a]) => Instruction::Out(reg(a)?),
+        ("exit", []) => Instruction::Exit,
+        ("out_number", [a]) => Instruction::OutNumber(reg(a)?),
+        ("in", [a]) => Instruction::In(reg(a)?),
+        ("in_number", [a]) => Instruction::InNumber(reg(a)?),
+        ("not", [a, "<-", b]) => Instruction::Not(reg(a)?, reg(b)?),
+        ("push", [a]) => Instruction::Push(reg(a)?),
+        ("pop", [a]) => Instruction::Pop(reg(a)?),
+        ("call", [imm]) => match parse_immediate(line, imm)? {
+            Imm::Value(value) => Instruction::Call(value),
+            Imm::Label(label) => return Ok(Statement::WithLabel(Instruction::Call(0), label)),
+        },
+        ("ret", []) => Instruction::Ret,
+        ("jmp", [imm]) => match parse_immediate(line, imm)? {
+            Imm::Value(offset) => Instruction::Jump(offset),
+            Imm::Label(label) => return Ok(Statement::WithLabel(Instruction::Jump(0), label)),
+        },
+        ("bz", [a, ",", imm]) => match parse_immediate(line, imm)? {
+            Imm::Value(offset) => Instruction::BranchZero(reg(a)?, offset),
+            Imm::Label(label) => {
+                return Ok(Statement::WithLabel(
+                    Instruction::BranchZero(reg(a)?, 0),
+                    label,
+                ))
+            }
+        },
+        ("bnz", [a, ",", imm]) => match parse_immediate(line, imm)? {
+            Imm::Value(offset) => Instruction::BranchNonZero(reg(a)?, offset),
+            Imm::Label(label) => {
+                return Ok(Statement::WithLabel(
+                    Instruction::BranchNonZero(reg(a)?, 0),
+                    label,
+                ))
+            }
+        },
+        ("rdctl", [a, "<-", b]) => Instruction::ReadControl(reg(a)?, control(b)?),
+        ("wrctl", [a, "<-", b]) => Instruction::WriteControl(control(a)?, reg(b)?),
+        ("rtt", []) => Instruction::ReturnFromTrap,
+        ("reti", []) => Instruction::ReturnFromInterrupt,
+        (
+            "move" | "store" | "load" | "loadimm" | "sub" | "out" | "exit" | "out_number" | "in"
+            | "in_number" | "not" | "push" | "pop" | "call" | "ret" | "jmp" | "bz" | "bnz"
+            | "rdctl" | "wrctl" | "rtt" | "reti",
+            _,
+        ) => return Err(invalid_operands()),
+        _ => return Err(syntax(line, format!("unknown instruction `{}`", mnemonic))),
+    };
+    Ok(Statement::Instruction(instruction))
+}
+
+// Replace the immediate of an instruction given with a label.
+fn with_immediate(instruction: Instruction, value: i16) -> Instruction {
+    match instruction {
+        Instruction::LoadImm(a, _) => Instruction::LoadImm(a, value),
+        Instruction::Call(_) => Instruction::Call(value),
+        Instruction::Jump(_) => Instruction::Jump(value),
+        Instruction::BranchZero(a, _) => Instruction::BranchZero(a, value),
+        Instruction::BranchNonZero(a, _) => Instruction::BranchNonZero(a, value),
+        _ => unreachable!("{} has no immediate", instruction),
+    }
+}
+
+// Constructor of the instructions written `mnemonic rA <- rB, rC`.
+fn binary_operation(mnemonic: &str) -> Option<fn(u8, u8, u8) -> Instruction> {
+    Some(match mnemonic {
+        "add" => Instruction::Add,
+        "mul" => Instruction::Mul,
+        "divs" => Instruction::DivS,
+        "divu" => Instruction::DivU,
+        "rems" => Instruction::RemS,
+        "remu" => Instruction::RemU,
+        "and" => Instruction::And,
+        "or" => Instruction::Or,
+        "xor" => Instruction::Xor,
+        "shl" => Instruction::Shl,
+        "shr" => Instruction::Shr,
+        "sar" => Instruction::Sar,
+        "slt" => Instruction::Slt,
+        "sltu" => Instruction::SltU,
+        _ => return None,
+    })
+}
+
+// Split operands into registers, immediates and punctuation.
+fn tokenize(operands: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut chars = operands.chars().peekable();
+    while let Some(c) = chars.next() {
+        let punctuation = match c {
+            '[' | ']' | ',' => Some(c.to_string()),
+            '<' if chars.peek() == Some(&'-') => {
+                chars.next();
+                Some("<-".to_string())
+            }
+            '!' if chars.peek() == Some(&'=') => {
+                chars.next();
+                Some("!=".to_string())
+            }
+            // A minus sign directly followed by a digit belongs to an immediate.
+            '-' if !current.starts_with('#') => Some("-".to_string()),
+            _ => None,
+        };
+        if punctuation.is_some() || c.is_whitespace() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            tokens.extend(punctuation);
+        } else {
+            current.push(c);
+        }
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+fn parse_register(line: usize, token: &str) -> Result<u8, AssemblerError> {
+    token
+        .strip_prefix('r')
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| syntax(line, format!("expected a register, found `{}`", token)))
+}
+
+fn parse_control_register(line: usize, token: &str) -> Result<u8, AssemblerError> {
+    token
+        .strip_prefix('c')
+        .and_then(|n| n.parse().ok())
+        .filter(|&n: &u8| (n as usize) < CONTROL_REGISTERS)
+        .ok_or_else(|| {
+            syntax(
+                line,
+                format!("expected a control register, found `{}`", token),
+            )
+        })
+}
+
+// Immediate operand, either a literal or a label reference.
+enum Imm {
+    Value(i16),
+    Label(String),
+}
+
+fn parse_immediate(line: usize, token: &str) -> Result<Imm, AssemblerError> {
+    let Some(imm) = token.strip_prefix('#') else {
+        return Err(syntax(
+            line,
+            format!("expected an immediate, found `{}`", token),
+        ));
+    };
+    if is_identifier(imm) {
+        return Ok(Imm::Label(imm.to_string()));
+    }
+    let value =
+        parse_number(imm).ok_or_else(|| syntax(line, format!("invalid immediate `{}`", token)))?;
+    i16::try_from(value)
+        .map(Imm::Value)
+        .map_err(|_| AssemblerError::ImmediateOutOfRange { line, value })
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let (radix, digits) = match digits.strip_prefix("0x") {
+        Some(hex) => (16, hex),
+        None => (10, digits),
+    };
+    // from_str_radix would accept a sign of its own, as in `--5`
+    if !digits.starts_with(|c: char| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = i64::from_str_radix(digits, radix).ok()?;
+    Some(if negative { -value } else { value })
+}
+
+// Parse a Python-like bytes literal such as `'Hello, world!\n'` (the
+// leading `b` has already been removed).
+fn parse_bytes_literal(line: usize, text: &str) -> Result<Vec<u8>, AssemblerError> {
+    let mut chars = text.chars();
+    let quote = chars.next().unwrap();
+    let mut bytes = Vec::new();
+    loop {
+        match chars.next() {
+            None => return Err(syntax(line, "unterminated bytes literal".to_string())),
+            Some(c) if c == quote => break,
+            Some('\\') => {
+                let byte = match chars.next() {
+                    Some('n') => b'\n',
+                    Some('t') => b'\t',
+                    Some('r') => b'\r',
+                    Some('0') => 0,
+                    Some('\\') => b'\\',
+                    Some('\'') => b'\'',
+                    Some('"') => b'"',
+                    Some('x') => {
+                        let hex: String = chars.by_ref().take(2).collect();
+                        u8::from_str_radix(&hex, 16)
+                            .map_err(|_| syntax(line, format!("invalid escape `\\x{}`", hex)))?
+                    }
+                    other => {
+                        return Err(syntax(
+                            line,
+                            format!("invalid escape `\\{}`", other.unwrap_or(' ')),
+                        ))
+                    }
+                };
+                bytes.push(byte);
+            }
+            Some(c) => {
+                let mut buf = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+    if !chars.as_str().trim().is_empty() {
+        return Err(syntax(
+            line,
+            "unexpected text after bytes literal".to_string(),
+        ));
+    }
+    Ok(bytes)
+}
+
+// Parse a list of bytes such as `[0, 0, 0x2a, 255]`.
+fn parse_byte_list(line: usize, text: &str) -> Result<Vec<u8>, AssemblerError> {
+    let inner = text
+        .strip_prefix('[')
+        .and_then(|t| t.strip_suffix(']'))
+        .ok_or_else(|| syntax(line, "unterminated byte list".to_string()))?;
+    if inner.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    inner
+        .split(',')
+        .map(|item| {
+            let item = item.trim();
+            parse_number(item)
+                .and_then(|value| u8::try_from(value).ok())
+                .ok_or_else(|| syntax(line, format!("invalid byte `{}`", item)))
+        })
+        .collect()
+}
@@ -0,0 +1,101 @@
+use crate::{Instruction, Symbols};
+use std::fmt;
+
+/// Return address found on the stack by
+/// [Machine::backtrace](crate::Machine::backtrace).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    /// Address of the stack word holding the return address.
+    pub stack: u32,
+    /// Address the caller resumes at.
+    pub return_address: u32,
+    /// Address of the instruction which transferred control to the callee:
+    /// a `call`, or the `loadimm r0 <- #function` of a call made by hand.
+    pub call: u32,
+}
+
+// Address of the call instruction ending right before `address` in
+// `memory`, if there is one.
+fn call_before(memory: &[u8], address: u32) -> Option<u32> {
+    let address = address as usize;
+    if address > memory.len() {
+        return None;
+    }
+    let decoded = |start: usize| Instruction::decode(&memory[start..address]).ok();
+    let call = Instruction::Call(0).size();
+    if address >= call && matches!(decoded(address - call), Some((Instruction::Call(_), _))) {
+        return Some((address - call) as u32);
+    }
+    let jump = Instruction::LoadImm(0, 0).size();
+    if address >= jump
+        && matches!(
+            decoded(address - jump),
+            Some((Instruction::LoadImm(0, _), _))
+        )
+    {
+        return Some((address - jump) as u32);
+    }
+    None
+}
+
+/// Walk the stack from `sp` to the end of `memory`, keeping the words
+/// which follow a call instruction, innermost call first.
+pub(crate) fn walk(memory: &[u8], sp: u32) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    let mut stack = sp as usize;
+    while stack + 4 <= memory.len() {
+        let word = u32::from_le_bytes(memory[stack..stack + 4].try_into().unwrap());
+        if let Some(call) = call_before(memory, word) {
+            frames.push(Frame {
+                stack: stack as u32,
+                return_address: word,
+                call,
+            });
+        }
+        stack += 4;
+    }
+    frames
+}
+
+/// Backtrace of a faulting instruction at `ip`, shown with the labels of
+/// `symbols` when one precedes the address:
+///
+/// ```text
+///   #0 0052 ite_then_1
+///   #1 0130 ite_then_2+19, returning to return_from_mult_1 [4080]
+///   #2 0019, returning to return_from_rfact_1 [4092]
+/// ```
+pub struct Backtrace<'a> {
+    /// Address of the instruction at the top of the backtrace.
+    pub ip: u32,
+    /// Frames of the callers, innermost first.
+    pub frames: &'a [Frame],
+    /// Labels of the program.
+    pub symbols: Option<&'a Symbols>,
+}
+
+impl fmt::Display for Backtrace<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |address: u32| match self.symbols {
+            Some(symbols) => symbols.name(address),
+            None => format!("{:04}", address),
+        };
+        // Address followed by its label, if one precedes it
+        let located = |address: u32| match self.symbols.and_then(|s| s.lookup(address)) {
+            Some(_) => format!("{:04} {}", address, name(address)),
+            None => format!("{:04}", address),
+        };
+        writeln!(f, "  #0 {}", located(self.ip))?;
+        for (i, frame) in self.frames.iter().enumerate() {
+            writeln!(
+                f,
+                "  #{} {}, returning to {} [{}]",
+                i + 1,
+                located(frame.call),
+                name(frame.return_address),
+                frame.stack
+            )?;
+        }
+        Ok(())
+    }
+}
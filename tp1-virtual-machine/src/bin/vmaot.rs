@@ -0,0 +1,16 @@
+use interpreter::translate;
+use std::process::exit;
+
+fn main() {
+    // Take a filename as argument on the command line
+    let Some(filename) = std::env::args().nth(1) else {
+        eprintln!("usage: vmaot <program.bin>");
+        exit(2)
+    };
+
+    let image = std::fs::read(&filename).unwrap_or_else(|e| {
+        eprintln!("{}: {}", filename, e);
+        exit(1)
+    });
+    print!("{}", translate(&image));
+}
@@ -0,0 +1,38 @@
+use interpreter::assemble;
+use std::path::PathBuf;
+use std::process::exit;
+
+fn usage() -> ! {
+    eprintln!("usage: vmasm <source.dis> [-o <output.bin>]");
+    exit(2)
+}
+
+fn main() {
+    // Take the source file and an optional output file on the command line
+    let mut args = std::env::args().skip(1);
+    let mut source = None;
+    let mut output = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = Some(PathBuf::from(args.next().unwrap_or_else(|| usage()))),
+            _ if source.is_none() => source = Some(PathBuf::from(arg)),
+            _ => usage(),
+        }
+    }
+    let source = source.unwrap_or_else(|| usage());
+    // By default, write `foo.bin` next to `foo.dis`
+    let output = output.unwrap_or_else(|| source.with_extension("bin"));
+
+    let text = std::fs::read_to_string(&source).unwrap_or_else(|e| {
+        eprintln!("{}: {}", source.display(), e);
+        exit(1)
+    });
+    let program = assemble(&text).unwrap_or_else(|e| {
+        eprintln!("{}: {}", source.display(), e);
+        exit(1)
+    });
+    if let Err(e) = std::fs::write(&output, &program.bytes) {
+        eprintln!("{}: {}", output.display(), e);
+        exit(1)
+    }
+}
@@ -0,0 +1,341 @@
+use interpreter::{Instruction, Machine, MachineConfig, MachineError};
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use std::process::exit;
+
+const HELP: &str = "\
+commands:
+  s, step [n]          execute n instructions (default 1)
+  c, continue          run until a breakpoint, the end of the program or an error
+  rs, reverse-step [n] undo n instructions (default 1)
+  rc, reverse-continue undo instructions until a breakpoint or the start of the program
+  b, break <addr>      set a breakpoint
+  d, delete <addr>     remove a breakpoint
+  info break           list breakpoints
+  regs                 show registers
+  x/<n> <addr>         show n 32-bit words of memory starting at addr
+  set r<i> <value>     change a register
+  set [<addr>] <value> change a 32-bit word of memory
+  l, disas [n]         disassemble n instructions (default 8) from IP, after a
+                       few before it
+  h, help              show this help
+  q, quit              leave the debugger
+Addresses and values are decimal or 0x-prefixed hexadecimal, and may be
+given as a register (r2). An empty line repeats the last command.
+Reverse commands need vmdb to be started with --record.";
+
+struct Debugger {
+    machine: Machine,
+    breakpoints: BTreeSet<u32>,
+    // Whether steps are recorded, so that they can be undone.
+    recording: bool,
+    // Set once the program has executed an exit instruction.
+    finished: bool,
+}
+
+enum Stop {
+    Breakpoint,
+    Finished,
+    Steps,
+}
+
+impl Debugger {
+    fn ip(&self) -> u32 {
+        self.machine.regs()[0]
+    }
+
+    // Execute up to `count` instructions, stopping early on a breakpoint
+    // (except on the first instruction) or at the end of the program.
+    fn run(&mut self, count: Option<u64>) -> Result<Stop, MachineError> {
+        let mut executed = 0;
+        loop {
+            if count.is_some_and(|count| executed >= count) {
+                return Ok(Stop::Steps);
+            }
+            if executed > 0 && self.breakpoints.contains(&self.ip()) {
+                return Ok(Stop::Breakpoint);
+            }
+            // The program shares the terminal with the debugger
+            let finished = self.machine.step();
+            io::stdout().flush().map_err(MachineError::IOError)?;
+            executed += 1;
+            if finished? {
+                self.finished = true;
+                return Ok(Stop::Finished);
+            }
+        }
+    }
+
+    fn resume(&mut self, count: Option<u64>) {
+        if self.finished {
+            println!("The program is not running.");
+            return;
+        }
+        match self.run(count) {
+            Ok(Stop::Finished) => println!("Program exited."),
+            Ok(Stop::Breakpoint) => {
+                println!("Breakpoint at {:04}", self.ip());
+                self.disassemble(1);
+            }
+            Ok(Stop::Steps) => self.disassemble(1),
+            Err(e) => {
+                println!("Error: {}", e);
+                self.disassemble(1);
+            }
+        }
+    }
+
+    // Undo up to `count` instructions, stopping early on a breakpoint or
+    // at the start of the program. Devices and the terminal are not
+    // rewound.
+    fn rewind(&mut self, count: Option<u64>) {
+        if !self.recording {
+            println!("Steps are not recorded, start vmdb with --record.");
+            return;
+        }
+        let mut undone = 0;
+        while count.is_none_or(|count| undone < count) {
+            if !self.machine.step_back() {
+                println!("At the start of the program.");
+                break;
+            }
+            self.finished = false;
+            undone += 1;
+            if count.is_none() && self.breakpoints.contains(&self.ip()) {
+                println!("Breakpoint at {:04}", self.ip());
+                break;
+            }
+        }
+        self.disassemble(1);
+    }
+
+    fn disassemble(&self, count: usize) {
+        self.disassemble_from(self.ip() as usize, count);
+    }
+
+    // Disassemble the instructions leading to the IP, as far as `before`
+    // instructions back, then `count` instructions from the IP.
+    fn list(&self, before: usize, count: usize) {
+        let (start, leading) = self.start_before_ip(before);
+        self.disassemble_from(start, leading + count);
+    }
+
+    // Earliest address from which at most `before` instructions, decoded
+    // one after the other, end right at the IP, along with their number.
+    // The code is not known, so this may show data as instructions.
+    fn start_before_ip(&self, before: usize) -> (usize, usize) {
+        let memory = self.machine.memory();
+        let ip = self.ip() as usize;
+        let leading = |start: usize| {
+            let mut address = start;
+            for count in 1..=before {
+                match Instruction::decode(&memory[address..]) {
+                    Ok((_, size)) if address + size <= ip => address += size,
+                    _ => return None,
+                }
+                if address == ip {
+                    return Some(count);
+                }
+            }
+            None
+        };
+        (ip.saturating_sub(4 * before)..ip.min(memory.len()))
+            .find_map(|start| leading(start).map(|count| (start, count)))
+            .unwrap_or((ip, 0))
+    }
+
+    fn disassemble_from(&self, start: usize, count: usize) {
+        let memory = self.machine.memory();
+        let mut address = start;
+        for _ in 0..count {
+            let marker = if address == self.ip() as usize {
+                "=>"
+            } else {
+                "  "
+            };
+            let breakpoint = if self.breakpoints.contains(&(address as u32)) {
+                '*'
+            } else {
+                ' '
+            };
+            match Instruction::decode(memory.get(address..).unwrap_or(&[])) {
+                Ok((instruction, size)) => {
+                    println!("{}{}{:04}   {}", marker, breakpoint, address, instruction);
+                    address += size;
+                }
+                Err(_) => {
+                    let byte = memory.get(address).map(|b| format!("{:#04x}", b));
+                    println!(
+                        "{}{}{:04}   ???? {}",
+                        marker,
+                        breakpoint,
+                        address,
+                        byte.unwrap_or_default()
+                    );
+                    break;
+                }
+            }
+        }
+    }
+
+    fn show_registers(&self) {
+        for (i, value) in self.machine.regs().iter().enumerate() {
+            print!("r{:<2} = {:#010x} {:>11}", i, value, *value as i32);
+            if i % 2 == 1 {
+                println!();
+            } else {
+                print!("    ");
+            }
+        }
+        if self.machine.regs().len() % 2 == 1 {
+            println!();
+        }
+    }
+
+    fn examine(&self, count: usize, address: u32) -> Result<(), String> {
+        let memory = self.machine.memory();
+        for i in 0..count {
+            let start = address as usize + 4 * i;
+            let Some(word) = memory.get(start..start + 4) else {
+                if i > 0 {
+                    println!();
+                }
+                return Err(format!("address {:#06x} is out of memory", start));
+            };
+            if i % 4 == 0 {
+                if i > 0 {
+                    println!();
+                }
+                print!("{:#06x}:", start);
+            }
+            print!(" {:#010x}", u32::from_le_bytes(word.try_into().unwrap()));
+        }
+        println!();
+        Ok(())
+    }
+
+    // Parse a number, or read the value of a register.
+    fn value(&self, text: &str) -> Result<u32, String> {
+        let parsed = if let Some(reg) = text.strip_prefix('r') {
+            reg.parse::<usize>()
+                .ok()
+                .and_then(|reg| self.machine.regs().get(reg).copied())
+        } else if let Some(hex) = text.strip_prefix("0x") {
+            u32::from_str_radix(hex, 16).ok()
+        } else if let Some(negative) = text.strip_prefix('-') {
+            negative.parse::<u32>().ok().map(|n| n.wrapping_neg())
+        } else {
+            text.parse().ok()
+        };
+        parsed.ok_or_else(|| format!("invalid value `{}`", text))
+    }
+
+    // Execute one command line; return false when the debugger must stop.
+    fn command(&mut self, line: &str) -> Result<bool, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match &words[..] {
+            [] => (),
+            ["s" | "step"] => self.resume(Some(1)),
+            ["s" | "step", n] => self.resume(Some(self.value(n)? as u64)),
+            ["c" | "continue"] => self.resume(None),
+            ["rs" | "reverse-step"] => self.rewind(Some(1)),
+            ["rs" | "reverse-step", n] => self.rewind(Some(self.value(n)? as u64)),
+            ["rc" | "reverse-continue"] => self.rewind(None),
+            ["b" | "break", address] => {
+                let address = self.value(address)?;
+                self.breakpoints.insert(address);
+                println!("Breakpoint set at {:04}", address);
+            }
+            ["d" | "delete", address] => {
+                let address = self.value(address)?;
+                if !self.breakpoints.remove(&address) {
+                    return Err(format!("no breakpoint at {:04}", address));
+                }
+            }
+            ["info", "break"] => {
+                for address in &self.breakpoints {
+                    println!("{:04}", address);
+                }
+            }
+            ["regs"] => self.show_registers(),
+            [x, address] if x.starts_with("x/") => {
+                let count = self.value(&x[2..])? as usize;
+                self.examine(count, self.value(address)?)?;
+            }
+            ["x", address] => self.examine(1, self.value(address)?)?,
+            ["set", target, value] => {
+                let value = self.value(value)?;
+                if let Some(address) = target.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+                    let address = self.value(address)? as usize;
+                    self.machine
+                        .set_memory(address, &value.to_le_bytes())
+                        .map_err(|_| format!("address {:#06x} is out of memory", address))?;
+                } else {
+                    let reg = target
+                        .strip_prefix('r')
+                        .and_then(|reg| reg.parse().ok())
+                        .ok_or_else(|| format!("invalid register `{}`", target))?;
+                    self.machine
+                        .set_reg(reg, value)
+                        .map_err(|_| format!("invalid register `{}`", target))?;
+                }
+            }
+            ["l" | "disas"] => self.list(3, 8),
+            ["l" | "disas", n] => self.list(3, self.value(n)? as usize),
+            ["h" | "help"] => println!("{}", HELP),
+            ["q" | "quit"] => return Ok(false),
+            _ => return Err(format!("unknown command `{}`, try `help`", line.trim())),
+        }
+        Ok(true)
+    }
+}
+
+fn main() {
+    // Take a filename as argument on the command line
+    let mut args = std::env::args().skip(1).peekable();
+    let recording = args.next_if_eq("--record").is_some();
+    let Some(filename) = args.next() else {
+        eprintln!("usage: vmdb [--record] <program.bin>");
+        exit(2)
+    };
+    let image = std::fs::read(&filename).unwrap_or_else(|e| {
+        eprintln!("{}: {}", filename, e);
+        exit(1)
+    });
+
+    let mut machine = Machine::with_config(&image, MachineConfig::default()).unwrap_or_else(|e| {
+        eprintln!("{}: {}", filename, e);
+        exit(1)
+    });
+    // Record every step if asked, so that they can be undone, as the record
+    // grows with every executed instruction
+    machine.set_recording(recording);
+    let mut debugger = Debugger {
+        machine,
+        breakpoints: BTreeSet::new(),
+        recording,
+        finished: false,
+    };
+    debugger.disassemble(1);
+
+    let stdin = io::stdin();
+    let mut last = String::new();
+    loop {
+        print!("(vmdb) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        // An empty line repeats the previous command
+        if line.trim().is_empty() {
+            line = last.clone();
+        }
+        match debugger.command(&line) {
+            Ok(true) => (),
+            Ok(false) => break,
+            Err(message) => println!("{}", message),
+        }
+        last = line;
+    }
+}
@@ -0,0 +1,321 @@
+use crate::cache::DecodeCache;
+use crate::fusion::Fused;
+use crate::{Instruction, MachineError, Region};
+
+/// A peripheral mapped into the address space of a machine with
+/// [Machine::attach](crate::Machine::attach).
+///
+/// The device occupies `size()` bytes starting at the address it is
+/// attached to, and `load` and `store` instructions accessing this range
+/// call `read32` and `write32` with the offset of the access from that
+/// address. An invalid access should return
+/// [MachineError::InvalidMemoryAccess] with this offset as its address: it
+/// is then reported at the absolute address.
+pub trait Device {
+    /// Number of bytes of the address space the device occupies.
+    fn size(&self) -> u32;
+
+    /// Read the 32-bit word at `offset`.
+    fn read32(&mut self, offset: u32) -> Result<u32, MachineError>;
+
+    /// Write the 32-bit word `value` at `offset`.
+    fn write32(&mut self, offset: u32, value: u32) -> Result<(), MachineError>;
+
+    /// Called once after every instruction executed by the machine.
+    fn tick(&mut self) {}
+
+    /// Whether the device requests an interrupt, on the line numbered after
+    /// the order in which devices are attached.
+    fn interrupt(&self) -> bool {
+        false
+    }
+
+    /// State of the device, saved in a [Snapshot](crate::Snapshot). Devices
+    /// without state save nothing.
+    fn save(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restore a state saved by `save`, or return
+    /// [MachineError::InvalidSnapshot] if it is not one.
+    fn restore(&mut self, state: &[u8]) -> Result<(), MachineError> {
+        if state.is_empty() {
+            Ok(())
+        } else {
+            Err(MachineError::InvalidSnapshot {
+                reason: "unexpected device state".to_owned(),
+            })
+        }
+    }
+}
+
+struct Mapping {
+    base: u32,
+    size: u32,
+    device: Box<dyn Device>,
+}
+
+/// Address space of a machine: RAM starting at address 0, and devices
+/// mapped above it. Data accesses are routed to either, while instructions
+/// are only fetched from RAM.
+pub(crate) struct Bus {
+    ram: Vec<u8>,
+    devices: Vec<Mapping>,
+    // Previous content of the RAM words written since the journal started
+    journal: Option<Vec<(u32, [u8; 4])>>,
+    // Number of words read and written so far
+    reads: u64,
+    writes: u64,
+    // Regions restricting accesses, in the order they were added
+    regions: Vec<Region>,
+    // Instructions already decoded, if caching them
+    decoded: Option<DecodeCache>,
+}
+
+impl Bus {
+    pub(crate) fn new(ram: Vec<u8>) -> Self {
+        Bus {
+            ram,
+            devices: Vec::new(),
+            journal: None,
+            reads: 0,
+            writes: 0,
+            regions: Vec::new(),
+            decoded: None,
+        }
+    }
+
+    pub(crate) fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    /// Mutable access to the RAM, which forgets the cached instructions.
+    pub(crate) fn ram_mut(&mut self) -> &mut [u8] {
+        if let Some(decoded) = &mut self.decoded {
+            decoded.clear();
+        }
+        &mut self.ram
+    }
+
+    /// Start or stop caching the decoded instructions.
+    pub(crate) fn set_decode_cache(&mut self, enabled: bool) {
+        self.decoded = enabled.then(|| DecodeCache::new(self.ram.len()));
+    }
+
+    /// Instruction cached at `address`, and its size.
+    #[inline]
+    pub(crate) fn decoded(&self, address: u32) -> Option<(Instruction, u32)> {
+        self.decoded.as_ref()?.get(address)
+    }
+
+    /// Sequence fused at `address`.
+    #[inline]
+    pub(crate) fn fused(&self, address: u32) -> Option<Fused> {
+        self.decoded.as_ref()?.get_fused(address)
+    }
+
+    /// Cache the sequence `fused` starting at `address`, if caching
+    /// instructions.
+    pub(crate) fn cache_fused(&mut self, address: u32, fused: Fused) {
+        if let Some(decoded) = &mut self.decoded {
+            decoded.insert_fused(address, fused);
+        }
+    }
+
+    /// Cache `instruction`, of `size` bytes, decoded at `address`, if
+    /// caching instructions.
+    pub(crate) fn cache(&mut self, address: u32, instruction: Instruction, size: usize) {
+        if let Some(decoded) = &mut self.decoded {
+            decoded.insert(address, instruction, size);
+        }
+    }
+
+    /// Map `device` at `base`.
+    ///
+    /// # Panics
+    /// This function panics when the device would overlap RAM or another
+    /// device, or extend past the end of the address space.
+    pub(crate) fn attach(&mut self, base: u32, device: Box<dyn Device>) {
+        let size = device.size();
+        let end = base as u64 + size as u64;
+        assert!(
+            base as u64 >= self.ram.len() as u64 && end <= 1 << 32,
+            "device at {:#x} does not fit above the RAM",
+            base
+        );
+        assert!(
+            self.devices
+                .iter()
+                .all(|m| end <= m.base as u64 || base as u64 >= m.base as u64 + m.size as u64),
+            "device at {:#x} overlaps another device",
+            base
+        );
+        self.devices.push(Mapping { base, size, device });
+    }
+
+    pub(crate) fn regions(&self) -> &[Region] {
+        &self.regions
+    }
+
+    pub(crate) fn protect(&mut self, region: Region) {
+        // Cached instructions within it may not be executable anymore
+        if let Some(decoded) = &mut self.decoded {
+            let range = &region.range;
+            decoded.invalidate(range.start, range.end.saturating_sub(range.start));
+        }
+        self.regions.push(region);
+    }
+
+    pub(crate) fn set_regions(&mut self, regions: Vec<Region>) {
+        if let Some(decoded) = &mut self.decoded {
+            decoded.clear();
+        }
+        self.regions = regions;
+    }
+
+    // First region containing one of the `width` bytes at `address`, and
+    // denying the access checked by `allowed`.
+    fn denied(&self, address: u32, width: u32, allowed: fn(&Region) -> bool) -> Option<&Region> {
+        self.regions
+            .iter()
+            .find(|r| r.overlaps(address, width) && !allowed(r))
+    }
+
+    /// Check that the instruction at `address` can be executed.
+    pub(crate) fn check_execute(&self, address: u32) -> Result<(), MachineError> {
+        match self.denied(address, 1, |r| r.permissions.execute) {
+            Some(region) => Err(MachineError::ExecuteProtected {
+                ip: address,
+                region: region.name.clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Read the 32-bit little-endian word at `address`.
+    pub(crate) fn read32(&mut self, address: u32) -> Result<u32, MachineError> {
+        self.reads += 1;
+        if let Some(region) = self.denied(address, 4, |r| r.permissions.read) {
+            return Err(MachineError::ReadProtected {
+                ip: 0,
+                address,
+                region: region.name.clone(),
+            });
+        }
+        if let Some(range) = self.ram_word(address) {
+            return Ok(u32::from_le_bytes(self.ram[range].try_into().unwrap()));
+        }
+        let mapping = self.device(address)?;
+        let base = mapping.base;
+        mapping
+            .device
+            .read32(address - base)
+            .map_err(|e| e.relocate(base))
+    }
+
+    /// Write the 32-bit little-endian word `value` at `address`.
+    pub(crate) fn write32(&mut self, address: u32, value: u32) -> Result<(), MachineError> {
+        self.writes += 1;
+        if let Some(region) = self.denied(address, 4, |r| r.permissions.write) {
+            return Err(MachineError::WriteProtected {
+                ip: 0,
+                address,
+                region: region.name.clone(),
+            });
+        }
+        if let Some(range) = self.ram_word(address) {
+            if let Some(journal) = &mut self.journal {
+                journal.push((address, self.ram[range.clone()].try_into().unwrap()));
+            }
+            if let Some(decoded) = &mut self.decoded {
+                decoded.invalidate(address, 4);
+            }
+            self.ram[range].copy_from_slice(&value.to_le_bytes());
+            return Ok(());
+        }
+        let mapping = self.device(address)?;
+        let base = mapping.base;
+        mapping
+            .device
+            .write32(address - base, value)
+            .map_err(|e| e.relocate(base))
+    }
+
+    /// Number of words read and written so far, including attempts at
+    /// invalid addresses.
+    pub(crate) fn accesses(&self) -> (u64, u64) {
+        (self.reads, self.writes)
+    }
+
+    /// Start recording the previous content of the RAM words written by
+    /// [write32](Bus::write32).
+    pub(crate) fn start_journal(&mut self) {
+        self.journal = Some(Vec::new());
+    }
+
+    /// Stop recording, and return the words recorded in the order they
+    /// were written. Writes to devices cannot be undone and are not
+    /// recorded.
+    pub(crate) fn take_journal(&mut self) -> Vec<(u32, [u8; 4])> {
+        self.journal.take().unwrap_or_default()
+    }
+
+    /// Let every device know that an instruction has been executed.
+    pub(crate) fn tick(&mut self) {
+        for mapping in &mut self.devices {
+            mapping.device.tick();
+        }
+    }
+
+    /// Interrupt lines requested by the first 32 devices.
+    pub(crate) fn pending(&self) -> u32 {
+        self.devices
+            .iter()
+            .take(32)
+            .enumerate()
+            .filter(|(_, m)| m.device.interrupt())
+            .fold(0, |lines, (n, _)| lines | 1 << n)
+    }
+
+    /// State of every device, in the order they were attached.
+    pub(crate) fn save(&self) -> Vec<Vec<u8>> {
+        self.devices.iter().map(|m| m.device.save()).collect()
+    }
+
+    /// Restore the devices from states saved by [save](Bus::save).
+    pub(crate) fn restore(&mut self, states: &[Vec<u8>]) -> Result<(), MachineError> {
+        if states.len() != self.devices.len() {
+            return Err(MachineError::InvalidSnapshot {
+                reason: format!(
+                    "{} device(s) saved, {} attached",
+                    states.len(),
+                    self.devices.len()
+                ),
+            });
+        }
+        for (mapping, state) in self.devices.iter_mut().zip(states) {
+            mapping.device.restore(state)?;
+        }
+        Ok(())
+    }
+
+    // Range of the word at `address` if it lies in RAM.
+    fn ram_word(&self, address: u32) -> Option<std::ops::Range<usize>> {
+        let start = address as usize;
+        (start + 4 <= self.ram.len()).then_some(start..start + 4)
+    }
+
+    // Device whose range contains the word at `address`.
+    fn device(&mut self, address: u32) -> Result<&mut Mapping, MachineError> {
+        self.devices
+            .iter_mut()
+            .find(|m| address >= m.base && address as u64 + 4 <= m.base as u64 + m.size as u64)
+            .ok_or(MachineError::InvalidMemoryAccess {
+                ip: 0,
+                opcode: None,
+                address,
+                width: 4,
+                host: false,
+            })
+    }
+}
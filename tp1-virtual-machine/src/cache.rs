@@ -0,0 +1,73 @@
+use crate::fusion::{Fused, MAX_FUSED_SIZE};
+use crate::Instruction;
+
+// Size of the longest instruction
+const MAX_SIZE: u32 = 4;
+
+/// Instructions decoded from RAM, with their size, indexed by their
+/// address. Only instructions which may be executed and whose registers
+/// exist are inserted, so that a cached instruction can be run without
+/// further checks. Sequences of such instructions which can be fused are
+/// cached at the address of their first instruction as well.
+pub(crate) struct DecodeCache {
+    entries: Vec<Option<(Instruction, u8)>>,
+    fused: Vec<Option<Fused>>,
+}
+
+impl DecodeCache {
+    /// Create an empty cache for a RAM of `size` bytes.
+    pub(crate) fn new(size: usize) -> Self {
+        DecodeCache {
+            entries: vec![None; size],
+            fused: vec![None; size],
+        }
+    }
+
+    /// Instruction decoded at `address`, and its size.
+    #[inline]
+    pub(crate) fn get(&self, address: u32) -> Option<(Instruction, u32)> {
+        match self.entries.get(address as usize) {
+            Some(&Some((instruction, size))) => Some((instruction, size as u32)),
+            _ => None,
+        }
+    }
+
+    /// Keep `instruction`, of `size` bytes, decoded at `address`.
+    pub(crate) fn insert(&mut self, address: u32, instruction: Instruction, size: usize) {
+        if let Some(entry) = self.entries.get_mut(address as usize) {
+            *entry = Some((instruction, size as u8));
+        }
+    }
+
+    /// Sequence fused at `address`.
+    #[inline]
+    pub(crate) fn get_fused(&self, address: u32) -> Option<Fused> {
+        self.fused.get(address as usize).copied().flatten()
+    }
+
+    /// Keep the sequence `fused` starting at `address`.
+    pub(crate) fn insert_fused(&mut self, address: u32, fused: Fused) {
+        if let Some(entry) = self.fused.get_mut(address as usize) {
+            *entry = Some(fused);
+        }
+    }
+
+    /// Forget the instructions and sequences overlapping the `width` bytes at `address`.
+    pub(crate) fn invalidate(&mut self, address: u32, width: u32) {
+        let start = address.saturating_sub(MAX_SIZE - 1) as usize;
+        let end = (address as u64 + width as u64).min(self.entries.len() as u64) as usize;
+        for entry in self.entries.iter_mut().take(end).skip(start) {
+            *entry = None;
+        }
+        let start = address.saturating_sub(MAX_FUSED_SIZE - 1) as usize;
+        for entry in self.fused.iter_mut().take(end).skip(start) {
+            *entry = None;
+        }
+    }
+
+    /// Forget every instruction.
+    pub(crate) fn clear(&mut self) {
+        self.entries.fill(None);
+        self.fused.fill(None);
+    }
+}
@@ -0,0 +1,62 @@
+//! Control registers, read with `rdctl` and written with `wrctl`.
+//!
+//! When [TRAP_VECTOR] holds a non-zero address, a fault caused by the
+//! program does not stop the machine. Instead, the address of the faulting
+//! instruction is saved into [TRAP_IP], the kind of fault into
+//! [TRAP_CAUSE] and its detail into [TRAP_VALUE], and execution continues
+//! at the handler. The handler returns with `rtt`, which jumps to the
+//! address in [TRAP_IP]: it must be advanced beforehand to skip the faulting
+//! instruction. A fault happening within the handler is not trapped.
+//!
+//! Devices request interrupts on numbered lines, shown in [IRQ_PENDING].
+//! Before executing an instruction, if a line is both pending and enabled
+//! in [IRQ_ENABLE], and [IRQ_VECTOR] holds a non-zero address, the IP is
+//! saved into [IRQ_IP] and execution continues at the interrupt handler.
+//! The handler returns with `reti`, which jumps back to the address in
+//! [IRQ_IP]. Interrupts are not taken while an interrupt or trap handler is
+//! running, and a line stays pending until its device is acknowledged.
+
+/// Address of the trap handler, or 0 when faults stop the machine.
+pub const TRAP_VECTOR: u8 = 0;
+/// Address of the instruction which caused the last trap.
+pub const TRAP_IP: u8 = 1;
+/// Cause of the last trap, one of the `CAUSE_*` codes.
+pub const TRAP_CAUSE: u8 = 2;
+/// Detail of the last trap: the address of an invalid memory access, the
+/// index of an invalid register, the opcode of an invalid instruction, the
+/// stack pointer of a stack fault, or the address denied by a region. It is
+/// 0 for other causes.
+pub const TRAP_VALUE: u8 = 3;
+
+/// Address of the interrupt handler, or 0 when interrupts are ignored.
+pub const IRQ_VECTOR: u8 = 4;
+/// Address of the instruction to resume at after the current interrupt.
+pub const IRQ_IP: u8 = 5;
+/// Interrupt lines that can interrupt the program: bit `n` enables line
+/// `n`. It is 0 after reset, which disables interrupts.
+pub const IRQ_ENABLE: u8 = 6;
+/// Interrupt lines currently requested by devices: bit `n` is set while
+/// line `n` is. Writes are ignored.
+pub const IRQ_PENDING: u8 = 7;
+
+/// Number of control registers.
+pub const CONTROL_REGISTERS: usize = 8;
+
+/// Trap cause of [MachineError::InvalidMemoryAccess](crate::MachineError::InvalidMemoryAccess).
+pub const CAUSE_INVALID_MEMORY: u32 = 1;
+/// Trap cause of [MachineError::InvalidRegisterAccess](crate::MachineError::InvalidRegisterAccess).
+pub const CAUSE_INVALID_REGISTER: u32 = 2;
+/// Trap cause of [MachineError::InvalidInstruction](crate::MachineError::InvalidInstruction).
+pub const CAUSE_INVALID_INSTRUCTION: u32 = 3;
+/// Trap cause of [MachineError::InvalidNumber](crate::MachineError::InvalidNumber).
+pub const CAUSE_INVALID_NUMBER: u32 = 4;
+/// Trap cause of [MachineError::DivisionByZero](crate::MachineError::DivisionByZero).
+pub const CAUSE_DIVISION_BY_ZERO: u32 = 5;
+/// Trap cause of [MachineError::StackOverflow](crate::MachineError::StackOverflow).
+pub const CAUSE_STACK_OVERFLOW: u32 = 6;
+/// Trap cause of [MachineError::StackUnderflow](crate::MachineError::StackUnderflow).
+pub const CAUSE_STACK_UNDERFLOW: u32 = 7;
+/// Trap cause of [MachineError::ReadProtected](crate::MachineError::ReadProtected),
+/// [MachineError::WriteProtected](crate::MachineError::WriteProtected) and
+/// [MachineError::ExecuteProtected](crate::MachineError::ExecuteProtected).
+pub const CAUSE_PROTECTION: u32 = 8;
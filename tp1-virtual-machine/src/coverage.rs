@@ -0,0 +1,157 @@
+use crate::symbols::instruction_address;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Executed instructions, gathered while a coverage is set with
+/// [Machine::set_coverage](crate::Machine::set_coverage).
+///
+/// The coverage of several runs is obtained by giving the coverage taken
+/// from a machine to the next one, or by merging coverages with
+/// [merge](Coverage::merge). Reports are made against the `.dis` listing of
+/// the program, line by line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    hits: BTreeMap<u32, u64>,
+}
+
+// Instruction of a listing, with the line number and name of the label
+// it belongs to.
+struct Line<'a> {
+    number: usize,
+    text: &'a str,
+    address: u32,
+    label: Option<(usize, &'a str)>,
+}
+
+// Instructions of a listing, numbering lines from 1.
+fn instructions(listing: &str) -> Vec<Line<'_>> {
+    let mut label = None;
+    let mut lines = Vec::new();
+    for (index, text) in listing.lines().enumerate() {
+        if let Some(name) = text.trim().strip_suffix(':') {
+            label = Some((index + 1, name));
+        } else if let Some(address) = instruction_address(text) {
+            lines.push(Line {
+                number: index + 1,
+                text,
+                address,
+                label,
+            });
+        }
+    }
+    lines
+}
+
+impl Coverage {
+    /// Create a coverage where nothing has been executed yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an instruction executed at `ip`.
+    pub(crate) fn record(&mut self, ip: u32) {
+        *self.hits.entry(ip).or_default() += 1;
+    }
+
+    /// Number of times the instruction at `address` was executed.
+    pub fn hits(&self, address: u32) -> u64 {
+        self.hits.get(&address).copied().unwrap_or(0)
+    }
+
+    /// Add the executions recorded in `other`.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (&address, &hits) in &other.hits {
+            *self.hits.entry(address).or_default() += hits;
+        }
+    }
+
+    /// Number of instructions of `listing` which were executed, and number
+    /// of instructions in it.
+    pub fn totals(&self, listing: &str) -> (usize, usize) {
+        let lines = instructions(listing);
+        let executed = lines.iter().filter(|l| self.hits(l.address) > 0).count();
+        (executed, lines.len())
+    }
+
+    /// Text report of the coverage of `listing`: the totals, the number of
+    /// instructions executed after each label, and the lines of the
+    /// instructions never executed, if any.
+    ///
+    /// ```text
+    /// 35 of 40 instructions executed (87.5%)
+    ///
+    /// labels:
+    ///   mult_loop              5/5
+    ///   ite_then_1             0/4
+    ///
+    /// not executed:
+    ///   17:   0052   sub r11 <- r11 - r13
+    /// ```
+    pub fn report(&self, listing: &str) -> String {
+        let lines = instructions(listing);
+        let (executed, total) = self.totals(listing);
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{} of {} instructions executed ({:.1}%)",
+            executed,
+            total,
+            100.0 * executed as f64 / total.max(1) as f64
+        )
+        .unwrap();
+        writeln!(out, "\nlabels:").unwrap();
+        let mut labels: Vec<(&str, usize, usize)> = Vec::new();
+        for line in &lines {
+            let Some((_, label)) = line.label else {
+                continue;
+            };
+            if labels.last().is_none_or(|&(last, _, _)| last != label) {
+                labels.push((label, 0, 0));
+            }
+            let (_, executed, total) = labels.last_mut().unwrap();
+            *executed += (self.hits(line.address) > 0) as usize;
+            *total += 1;
+        }
+        for (label, executed, total) in labels {
+            let count = format!("{}/{}", executed, total);
+            writeln!(out, "  {:<20} {:>5}", label, count).unwrap();
+        }
+        if executed < total {
+            writeln!(out, "\nnot executed:").unwrap();
+        }
+        for line in lines.iter().filter(|l| self.hits(l.address) == 0) {
+            writeln!(out, "  {}: {}", line.number, line.text).unwrap();
+        }
+        out
+    }
+
+    /// Report of the coverage of `listing` in the lcov tracefile format,
+    /// naming it `source`. Labels are reported as functions, executed as
+    /// many times as the instruction following them.
+    pub fn lcov(&self, source: &str, listing: &str) -> String {
+        let lines = instructions(listing);
+        let mut out = String::new();
+        writeln!(out, "TN:\nSF:{}", source).unwrap();
+        // The first instruction of each label
+        let entries: Vec<(usize, &str, u32)> = lines
+            .iter()
+            .enumerate()
+            .filter(|&(i, l)| i == 0 || lines[i - 1].label != l.label)
+            .filter_map(|(_, l)| l.label.map(|(number, name)| (number, name, l.address)))
+            .collect();
+        for (number, name, _) in &entries {
+            writeln!(out, "FN:{},{}", number, name).unwrap();
+        }
+        for (_, name, address) in &entries {
+            writeln!(out, "FNDA:{},{}", self.hits(*address), name).unwrap();
+        }
+        let entered = entries.iter().filter(|e| self.hits(e.2) > 0).count();
+        writeln!(out, "FNF:{}\nFNH:{}", entries.len(), entered).unwrap();
+        for line in &lines {
+            writeln!(out, "DA:{},{}", line.number, self.hits(line.address)).unwrap();
+        }
+        let (executed, total) = self.totals(listing);
+        writeln!(out, "LF:{}\nLH:{}\nend_of_record", total, executed).unwrap();
+        out
+    }
+}
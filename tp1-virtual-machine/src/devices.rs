@@ -0,0 +1,256 @@
+use crate::{Device, MachineError};
+use std::io::{self, Read, Write};
+
+/// Address at which the CLI attaches the [Console], reachable with
+/// `loadimm rX <- #-4096`.
+pub const CONSOLE_BASE: u32 = 0xffff_f000;
+/// Address at which the CLI attaches the [Timer], reachable with
+/// `loadimm rX <- #-4080`.
+pub const TIMER_BASE: u32 = 0xffff_f010;
+/// Interrupt line of the [Timer] attached by the CLI.
+pub const TIMER_IRQ: u32 = 1;
+/// Address at which the CLI attaches the [Framebuffer], reachable with
+/// `loadimm rX <- #-3840`.
+pub const FRAMEBUFFER_BASE: u32 = 0xffff_f100;
+
+/// Number of bytes of an 8x8 RGB image.
+pub const FRAME_SIZE: usize = 192;
+
+fn invalid_offset(offset: u32) -> MachineError {
+    MachineError::InvalidMemoryAccess {
+        ip: 0,
+        opcode: None,
+        address: offset,
+        width: 4,
+        host: false,
+    }
+}
+
+fn invalid_state() -> MachineError {
+    MachineError::InvalidSnapshot {
+        reason: "invalid device state".to_owned(),
+    }
+}
+
+// Little-endian words making up the state of a device.
+fn words<const N: usize>(state: &[u8]) -> Result<[u32; N], MachineError> {
+    if state.len() != 4 * N {
+        return Err(invalid_state());
+    }
+    Ok(std::array::from_fn(|i| {
+        u32::from_le_bytes(state[4 * i..4 * i + 4].try_into().unwrap())
+    }))
+}
+
+/// Character console, with two registers:
+///
+/// - `0`, data: writing outputs the character held in the 8 low bits of the
+///   value, and reading inputs one byte, or -1 at the end of the input.
+/// - `4`, number: writing outputs the value as a signed decimal number.
+pub struct Console {
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+}
+
+impl Console {
+    /// Create a console printing on `output`, whose input is empty.
+    pub fn new(output: impl Write + 'static) -> Self {
+        Console {
+            input: Box::new(io::empty()),
+            output: Box::new(output),
+        }
+    }
+
+    /// Read the input of the console from `input`.
+    pub fn with_input(mut self, input: impl Read + 'static) -> Self {
+        self.input = Box::new(input);
+        self
+    }
+}
+
+impl Device for Console {
+    fn size(&self) -> u32 {
+        8
+    }
+
+    fn read32(&mut self, offset: u32) -> Result<u32, MachineError> {
+        match offset {
+            0 => {
+                let mut byte = [0];
+                loop {
+                    match self.input.read(&mut byte) {
+                        Ok(0) => return Ok(u32::MAX),
+                        Ok(_) => return Ok(byte[0] as u32),
+                        Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                        Err(e) => return Err(MachineError::IOError(e)),
+                    }
+                }
+            }
+            4 => Ok(0),
+            _ => Err(invalid_offset(offset)),
+        }
+    }
+
+    fn write32(&mut self, offset: u32, value: u32) -> Result<(), MachineError> {
+        match offset {
+            0 => self.output.write_all(&[value as u8])?,
+            4 => write!(self.output, "{}", value as i32)?,
+            _ => return Err(invalid_offset(offset)),
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+/// Counter of executed instructions, with three registers:
+///
+/// - `0`, count: number of instructions executed since it was last
+///   written.
+/// - `4`, limit: when non-zero, the count at which the timer expires.
+/// - `8`, status: 1 once the timer has expired, 0 otherwise. Writing
+///   clears it and restarts the count from 0.
+///
+/// The timer requests an interrupt while it is expired.
+#[derive(Debug, Default)]
+pub struct Timer {
+    count: u32,
+    limit: u32,
+    expired: bool,
+}
+
+impl Timer {
+    /// Create a stopped timer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Device for Timer {
+    fn size(&self) -> u32 {
+        12
+    }
+
+    fn read32(&mut self, offset: u32) -> Result<u32, MachineError> {
+        match offset {
+            0 => Ok(self.count),
+            4 => Ok(self.limit),
+            8 => Ok(self.expired as u32),
+            _ => Err(invalid_offset(offset)),
+        }
+    }
+
+    fn write32(&mut self, offset: u32, value: u32) -> Result<(), MachineError> {
+        match offset {
+            0 => self.count = value,
+            4 => self.limit = value,
+            8 => {
+                self.expired = false;
+                self.count = 0;
+            }
+            _ => return Err(invalid_offset(offset)),
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self) {
+        self.count = self.count.wrapping_add(1);
+        if self.limit != 0 && self.count == self.limit {
+            self.expired = true;
+        }
+    }
+
+    fn interrupt(&self) -> bool {
+        self.expired
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(12);
+        state.extend(self.count.to_le_bytes());
+        state.extend(self.limit.to_le_bytes());
+        state.extend((self.expired as u32).to_le_bytes());
+        state
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), MachineError> {
+        let [count, limit, expired] = words(state)?;
+        self.count = count;
+        self.limit = limit;
+        self.expired = expired != 0;
+        Ok(())
+    }
+}
+
+/// 8x8 RGB framebuffer, laid out like the images of the LED matrix: one
+/// red, green and blue byte per pixel, row after row. Its registers are:
+///
+/// - `0` to `191`: the image, accessed by 32-bit little-endian words at any
+///   offset.
+/// - `192`, present: writing sends the image, and reading gives the number
+///   of images sent.
+///
+/// Images are sent as SE203 records, a `0xff` byte followed by the 192
+/// bytes of the image. As `0xff` marks the start of a record, pixel bytes
+/// of `0xff` are sent as `0xfe`.
+pub struct Framebuffer {
+    image: [u8; FRAME_SIZE],
+    frames: u32,
+    output: Box<dyn Write>,
+}
+
+impl Framebuffer {
+    /// Create a black framebuffer sending its images to `output`.
+    pub fn new(output: impl Write + 'static) -> Self {
+        Framebuffer {
+            image: [0; FRAME_SIZE],
+            frames: 0,
+            output: Box::new(output),
+        }
+    }
+}
+
+impl Device for Framebuffer {
+    fn size(&self) -> u32 {
+        FRAME_SIZE as u32 + 4
+    }
+
+    fn read32(&mut self, offset: u32) -> Result<u32, MachineError> {
+        match offset as usize {
+            FRAME_SIZE => Ok(self.frames),
+            o if o + 4 <= FRAME_SIZE => {
+                Ok(u32::from_le_bytes(self.image[o..o + 4].try_into().unwrap()))
+            }
+            _ => Err(invalid_offset(offset)),
+        }
+    }
+
+    fn write32(&mut self, offset: u32, value: u32) -> Result<(), MachineError> {
+        match offset as usize {
+            FRAME_SIZE => {
+                let mut record = Vec::with_capacity(FRAME_SIZE + 1);
+                record.push(0xff);
+                record.extend(self.image.iter().map(|&b| b.min(0xfe)));
+                self.output.write_all(&record)?;
+                self.output.flush()?;
+                self.frames = self.frames.wrapping_add(1);
+            }
+            o if o + 4 <= FRAME_SIZE => self.image[o..o + 4].copy_from_slice(&value.to_le_bytes()),
+            _ => return Err(invalid_offset(offset)),
+        }
+        Ok(())
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut state = self.image.to_vec();
+        state.extend(self.frames.to_le_bytes());
+        state
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Result<(), MachineError> {
+        if state.len() != FRAME_SIZE + 4 {
+            return Err(invalid_state());
+        }
+        self.image.copy_from_slice(&state[..FRAME_SIZE]);
+        self.frames = u32::from_le_bytes(state[FRAME_SIZE..].try_into().unwrap());
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{Instruction, IRQ_VECTOR, TRAP_VECTOR};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+const IP: u8 = 0;
+const SP: u8 = 2;
+
+/// Produce a listing of `image` in the format of the `.dis` files.
+///
+/// Code is discovered by following the control flow from address 0:
+/// `loadimm r0 <- #x` jumps to `x`, `move r0 <- rB if rC != 0` jumps to
+/// the value last loaded into `rB` by a `loadimm` of the same block, and
+/// a value pushed with `store [r2] <- rX` or `push rX` is taken as a
+/// return address, and `call #x` calls `x` and then continues. Relative
+/// jumps and branches are followed as well, and so is a value written into
+/// the trap or interrupt vector.
+/// Those targets receive synthetic labels (`loc_0052`). Unreachable bytes
+/// lying between two pieces of code are shown as (dead) code if they decode
+/// exactly into instructions. Other bytes are shown as data, labelled
+/// (`data_0148`) when a `loadimm` refers to their start.
+pub fn disassemble(image: &[u8]) -> String {
+    let (mut code, targets) = find_code(image);
+    add_dead_code(image, &mut code);
+
+    // Data regions are the maximal runs of bytes not covered by code.
+    let mut covered = vec![false; image.len()];
+    for (&address, &(_, size)) in &code {
+        covered[address..address + size].fill(true);
+    }
+
+    // Label every jump target, and every data region referenced by a loadimm.
+    let mut labels: BTreeMap<usize, String> = targets
+        .into_iter()
+        .filter(|target| code.contains_key(target))
+        .map(|target| (target, format!("loc_{:04}", target)))
+        .collect();
+    for &(instruction, _) in code.values() {
+        if let Instruction::LoadImm(_, value) = instruction {
+            let value = value as u16 as usize;
+            if value < image.len() && !covered[value] {
+                labels.insert(value, format!("data_{:04}", value));
+            }
+        }
+    }
+
+    let mut listing = String::new();
+    let mut address = 0;
+    while address < image.len() {
+        if let Some(label) = labels.get(&address) {
+            writeln!(listing, "{}:", label).unwrap();
+        }
+        if let Some(&(instruction, size)) = code.get(&address) {
+            writeln!(
+                listing,
+                "  {:04}   {}",
+                address,
+                format(instruction, address, &labels)
+            )
+            .unwrap();
+            address += size;
+        } else {
+            // Data extends until the next code or the next label.
+            let end = (address + 1..image.len())
+                .find(|&a| covered[a] || labels.contains_key(&a))
+                .unwrap_or(image.len());
+            writeln!(listing, "  ???? {}", format_data(&image[address..end])).unwrap();
+            address = end;
+        }
+    }
+    listing
+}
+
+// Map every reachable instruction address to its decoded form and size,
+// and collect the addresses that control is transferred to.
+fn find_code(image: &[u8]) -> (BTreeMap<usize, (Instruction, usize)>, BTreeSet<usize>) {
+    let mut code = BTreeMap::new();
+    let mut covered = BTreeSet::new();
+    let mut targets = BTreeSet::new();
+    let mut pending = vec![0];
+    while let Some(start) = pending.pop() {
+        if start != 0 {
+            targets.insert(start);
+        }
+        // Values loaded by loadimm in the current block, to resolve jumps.
+        let mut constants: [Option<u16>; 256] = [None; 256];
+        let mut address = start;
+        while address < image.len() && !code.contains_key(&address) && !covered.contains(&address) {
+            let Some((instruction, size)) = Instruction::decode(&image[address..]).ok() else {
+                break;
+            };
+            if (address..address + size).any(|a| covered.contains(&a)) {
+                break;
+            }
+            code.insert(address, (instruction, size));
+            covered.extend(address..address + size);
+            let next = address + size;
+            match instruction {
+                Instruction::LoadImm(IP, value) => {
+                    pending.push(value as u16 as usize);
+                    break;
+                }
+                Instruction::LoadImm(a, value) => constants[a as usize] = Some(value as u16),
+                Instruction::MoveIf(IP, b, _) => {
+                    pending.extend(constants[b as usize].map(usize::from));
+                }
+                Instruction::Store(SP, b)
+                | Instruction::Push(b)
+                | Instruction::WriteControl(TRAP_VECTOR | IRQ_VECTOR, b) => {
+                    pending.extend(constants[b as usize].map(usize::from))
+                }
+                Instruction::Call(target) => pending.push(target as u16 as usize),
+                Instruction::Jump(offset) => {
+                    pending.extend(relative(next, offset));
+                    break;
+                }
+                Instruction::BranchZero(_, offset) | Instruction::BranchNonZero(_, offset) => {
+                    pending.extend(relative(next, offset))
+                }
+                Instruction::Load(IP, _)
+                | Instruction::Pop(IP)
+                | Instruction::Ret
+                | Instruction::ReturnFromTrap
+                | Instruction::ReturnFromInterrupt
+                | Instruction::Exit => break,
+                _ => {
+                    if let Some(a) = instruction.destination() {
+                        constants[a as usize] = None;
+                    }
+                }
+            }
+            address = next;
+        }
+    }
+    (code, targets)
+}
+
+// Target of a jump by `offset` from `next`, if it is a valid address.
+fn relative(next: usize, offset: i16) -> Option<usize> {
+    next.checked_add_signed(offset as isize)
+}
+
+// Decode the gaps between reachable instructions, as compilers often leave
+// unreachable instructions after a return.
+fn add_dead_code(image: &[u8], code: &mut BTreeMap<usize, (Instruction, usize)>) {
+    let mut gaps = Vec::new();
+    let mut end = 0;
+    for (&address, &(_, size)) in code.iter() {
+        if address > end {
+            gaps.push((end, address));
+        }
+        end = address + size;
+    }
+    for (start, end) in gaps {
+        let mut instructions = Vec::new();
+        let mut address = start;
+        while let Ok((instruction, size)) = Instruction::decode(&image[address..end]) {
+            instructions.push((address, (instruction, size)));
+            address += size;
+        }
+        if address == end {
+            code.extend(instructions);
+        }
+    }
+}
+
+// Show a loadimm, call, jump or branch operand as a label when there is
+// one at the address it designates.
+fn format(instruction: Instruction, address: usize, labels: &BTreeMap<usize, String>) -> String {
+    let next = address + instruction.size();
+    let target = |offset: i16| labels.get(&relative(next, offset)?);
+    let label = |value: i16| labels.get(&usize::try_from(value).ok()?);
+    match instruction {
+        Instruction::LoadImm(a, value) => match label(value) {
+            Some(label) => format!("loadimm r{} <- #{}", a, label),
+            None => instruction.to_string(),
+        },
+        Instruction::Call(value) => match label(value) {
+            Some(label) => format!("call #{}", label),
+            None => instruction.to_string(),
+        },
+        Instruction::Jump(offset) => match target(offset) {
+            Some(label) => format!("jmp #{}", label),
+            None => instruction.to_string(),
+        },
+        Instruction::BranchZero(a, offset) => match target(offset) {
+            Some(label) => format!("bz r{}, #{}", a, label),
+            None => instruction.to_string(),
+        },
+        Instruction::BranchNonZero(a, offset) => match target(offset) {
+            Some(label) => format!("bnz r{}, #{}", a, label),
+            None => instruction.to_string(),
+        },
+        _ => instruction.to_string(),
+    }
+}
+
+// Show text as a bytes literal, and anything else as a list of bytes.
+fn format_data(data: &[u8]) -> String {
+    let printable = data
+        .iter()
+        .all(|&b| (b' '..=b'~').contains(&b) || b == b'\n' || b == b'\t' || b == b'\r');
+    if !printable {
+        return format!("{:?}", data);
+    }
+    let quote = if data.contains(&b'\'') && !data.contains(&b'"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut text = format!("b{}", quote);
+    for &b in data {
+        match b {
+            b'\n' => text.push_str("\\n"),
+            b'\t' => text.push_str("\\t"),
+            b'\r' => text.push_str("\\r"),
+            b'\\' => text.push_str("\\\\"),
+            _ if b as char == quote => {
+                text.push('\\');
+                text.push(quote);
+            }
+            _ => text.push(b as char),
+        }
+    }
+    text.push(quote);
+    text
+}
@@ -0,0 +1,248 @@
+use crate::control::*;
+use std::{fmt, io};
+
+/// Errors stopping the machine. Faults caused by an instruction record the
+/// address of that instruction (`ip`) and its opcode. The opcode is `None`
+/// when it could not be read, or when the fault comes from a host call
+/// such as [Machine::set_reg](crate::Machine::set_reg), which sets `host`.
+#[derive(Debug)]
+pub enum MachineError {
+    /// The program tried to access `width` bytes at an invalid memory
+    /// address.
+    InvalidMemoryAccess {
+        ip: u32,
+        opcode: Option<u8>,
+        address: u32,
+        width: u32,
+        host: bool,
+    },
+    /// The program tried to access an invalid register.
+    InvalidRegisterAccess {
+        ip: u32,
+        opcode: Option<u8>,
+        register: usize,
+        host: bool,
+    },
+    /// The program tried to execute an invalid instruction.
+    InvalidInstruction { ip: u32, opcode: u8 },
+    /// The program failed to read its input or to write its output.
+    IOError(io::Error),
+    /// The program did not terminate within the allowed number of steps.
+    StepLimitExceeded { steps: u64, ip: u32 },
+    /// The program image is larger than the machine memory.
+    ImageTooLarge { size: usize, memory_size: usize },
+    /// The machine memory is larger than what 32-bit addresses can reach.
+    MemoryTooLarge { memory_size: usize },
+    /// The program tried to read a number, but the input did not contain
+    /// one.
+    InvalidNumber { ip: u32 },
+    /// The program tried to divide by zero.
+    DivisionByZero { ip: u32 },
+    /// The program tried to push below the stack limit, with the stack
+    /// pointer r2 at `sp`.
+    StackOverflow { ip: u32, sp: u32 },
+    /// The program tried to pop from an empty stack, with the stack pointer
+    /// r2 at `sp`.
+    StackUnderflow { ip: u32, sp: u32 },
+    /// The program tried to read the word at `address`, which lies in a
+    /// region which cannot be read.
+    ReadProtected {
+        ip: u32,
+        address: u32,
+        region: String,
+    },
+    /// The program tried to write the word at `address`, which lies in a
+    /// region which cannot be written.
+    WriteProtected {
+        ip: u32,
+        address: u32,
+        region: String,
+    },
+    /// The program tried to execute an instruction lying in a region whose
+    /// content cannot be executed.
+    ExecuteProtected { ip: u32, region: String },
+    /// A snapshot could not be loaded, or does not match the machine it is
+    /// restored into.
+    InvalidSnapshot { reason: String },
+}
+
+impl MachineError {
+    /// Attribute the fault to the instruction at `ip` with the given opcode.
+    pub(crate) fn in_instruction(mut self, at: u32, code: u8) -> Self {
+        match &mut self {
+            MachineError::InvalidMemoryAccess { ip, opcode, .. }
+            | MachineError::InvalidRegisterAccess { ip, opcode, .. } => {
+                *ip = at;
+                *opcode = Some(code);
+            }
+            MachineError::InvalidInstruction { ip, .. }
+            | MachineError::InvalidNumber { ip }
+            | MachineError::DivisionByZero { ip }
+            | MachineError::StackOverflow { ip, .. }
+            | MachineError::StackUnderflow { ip, .. }
+            | MachineError::ReadProtected { ip, .. }
+            | MachineError::WriteProtected { ip, .. } => *ip = at,
+            _ => (),
+        }
+        self
+    }
+
+    /// Address of the instruction which caused the error, if it comes from
+    /// the program rather than from the host.
+    pub fn ip(&self) -> Option<u32> {
+        match *self {
+            MachineError::StepLimitExceeded { ip, .. } => Some(ip),
+            MachineError::InvalidMemoryAccess { host: true, .. }
+            | MachineError::InvalidRegisterAccess { host: true, .. } => None,
+            _ => self.trap().map(|(ip, _, _)| ip),
+        }
+    }
+
+    /// Address of the faulting instruction, trap cause and trap value of a
+    /// fault which can be trapped, as documented in [crate::control].
+    pub(crate) fn trap(&self) -> Option<(u32, u32, u32)> {
+        match *self {
+            MachineError::InvalidMemoryAccess { ip, address, .. } => {
+                Some((ip, CAUSE_INVALID_MEMORY, address))
+            }
+            MachineError::InvalidRegisterAccess { ip, register, .. } => {
+                Some((ip, CAUSE_INVALID_REGISTER, register as u32))
+            }
+            MachineError::InvalidInstruction { ip, opcode } => {
+                Some((ip, CAUSE_INVALID_INSTRUCTION, opcode as u32))
+            }
+            MachineError::InvalidNumber { ip } => Some((ip, CAUSE_INVALID_NUMBER, 0)),
+            MachineError::DivisionByZero { ip } => Some((ip, CAUSE_DIVISION_BY_ZERO, 0)),
+            MachineError::StackOverflow { ip, sp } => Some((ip, CAUSE_STACK_OVERFLOW, sp)),
+            MachineError::StackUnderflow { ip, sp } => Some((ip, CAUSE_STACK_UNDERFLOW, sp)),
+            MachineError::ReadProtected { ip, address, .. }
+            | MachineError::WriteProtected { ip, address, .. } => {
+                Some((ip, CAUSE_PROTECTION, address))
+            }
+            MachineError::ExecuteProtected { ip, .. } => Some((ip, CAUSE_PROTECTION, ip)),
+            MachineError::IOError(_)
+            | MachineError::StepLimitExceeded { .. }
+            | MachineError::ImageTooLarge { .. }
+            | MachineError::MemoryTooLarge { .. }
+            | MachineError::InvalidSnapshot { .. } => None,
+        }
+    }
+
+    /// Move a fault found within a part of the address space, such as a
+    /// slice of memory being decoded or a device, to the address where this
+    /// part starts.
+    pub(crate) fn relocate(mut self, offset: u32) -> Self {
+        match &mut self {
+            MachineError::InvalidMemoryAccess { ip, address, .. } => {
+                *ip = ip.wrapping_add(offset);
+                *address = address.wrapping_add(offset);
+            }
+            MachineError::InvalidRegisterAccess { ip, .. }
+            | MachineError::InvalidInstruction { ip, .. } => *ip = ip.wrapping_add(offset),
+            _ => (),
+        }
+        self
+    }
+}
+
+impl From<io::Error> for MachineError {
+    fn from(error: io::Error) -> Self {
+        MachineError::IOError(error)
+    }
+}
+
+impl fmt::Display for MachineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MachineError::InvalidMemoryAccess {
+                ip,
+                opcode,
+                address,
+                width,
+                ..
+            } => {
+                write!(
+                    f,
+                    "invalid memory access of {} byte(s) at address {:#x}",
+                    width, address
+                )?;
+                if let Some(opcode) = opcode {
+                    write!(f, " by instruction {} at {:04}", opcode, ip)?;
+                }
+                Ok(())
+            }
+            MachineError::InvalidRegisterAccess {
+                ip,
+                opcode,
+                register,
+                ..
+            } => {
+                write!(f, "invalid register r{}", register)?;
+                if let Some(opcode) = opcode {
+                    write!(f, " used by instruction {} at {:04}", opcode, ip)?;
+                }
+                Ok(())
+            }
+            MachineError::InvalidInstruction { ip, opcode } => {
+                write!(f, "invalid instruction {} at {:04}", opcode, ip)
+            }
+            MachineError::IOError(error) => write!(f, "I/O error: {}", error),
+            MachineError::StepLimitExceeded { steps, ip } => write!(
+                f,
+                "program still running after {} steps, at {:04}",
+                steps, ip
+            ),
+            MachineError::ImageTooLarge { size, memory_size } => write!(
+                f,
+                "program of {} bytes does not fit in {} bytes of memory",
+                size, memory_size
+            ),
+            MachineError::MemoryTooLarge { memory_size } => write!(
+                f,
+                "memory of {} bytes cannot be addressed with 32 bits",
+                memory_size
+            ),
+            MachineError::InvalidNumber { ip } => {
+                write!(f, "no number found in the input read at {:04}", ip)
+            }
+            MachineError::DivisionByZero { ip } => write!(f, "division by zero at {:04}", ip),
+            MachineError::StackOverflow { ip, sp } => {
+                write!(f, "stack overflow at {:04}, with r2 at {}", ip, sp)
+            }
+            MachineError::StackUnderflow { ip, sp } => {
+                write!(f, "stack underflow at {:04}, with r2 at {}", ip, sp)
+            }
+            MachineError::ReadProtected {
+                ip,
+                address,
+                region,
+            } => write!(
+                f,
+                "read at address {:#x} denied by region {} at {:04}",
+                address, region, ip
+            ),
+            MachineError::WriteProtected {
+                ip,
+                address,
+                region,
+            } => write!(
+                f,
+                "write at address {:#x} denied by region {} at {:04}",
+                address, region, ip
+            ),
+            MachineError::ExecuteProtected { ip, region } => {
+                write!(f, "execution denied by region {} at {:04}", region, ip)
+            }
+            MachineError::InvalidSnapshot { reason } => write!(f, "invalid snapshot: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for MachineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MachineError::IOError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
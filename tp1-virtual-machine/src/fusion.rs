@@ -0,0 +1,83 @@
+use crate::Instruction;
+
+/// Sequence of instructions generated over and over by the compiler, run
+/// as a single operation by the decode cache. Every register written by
+/// the sequence, scratch registers included, ends up with the value the
+/// instructions would have given it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Fused {
+    /// `loadimm rX <- #imm; sub rA <- rB - rX`, such as the stack
+    /// adjustment `loadimm r3 <- #4; sub r2 <- r2 - r3` before a push.
+    LoadImmSub { x: u8, imm: i16, a: u8, b: u8 },
+    /// `loadimm rX <- #target; move r0 <- rX if rC`, a conditional jump.
+    JumpIf { x: u8, target: i16, c: u8 },
+    /// `loadimm rX <- #-4; sub rS <- rS - rX; loadimm rX <- #4;
+    /// sub rX <- rS - rX; load r0 <- [rX]`, a return popping the address
+    /// at rS.
+    Return { x: u8, s: u8 },
+}
+
+// Number of instructions of the longest sequence
+const LONGEST: usize = 5;
+
+/// Number of bytes of the longest sequence.
+pub(crate) const MAX_FUSED_SIZE: u32 = 19;
+
+impl Fused {
+    /// Sequence starting at `address` in `memory`, if there is one, along
+    /// with its instructions and their addresses. Sequences using r0 other
+    /// than to jump are not fused, as the instructions would see the IP
+    /// change between them.
+    pub(crate) fn detect(memory: &[u8], address: u32) -> Option<(Fused, Vec<(u32, Instruction)>)> {
+        let mut instructions = Vec::with_capacity(LONGEST);
+        let mut next = address as usize;
+        while instructions.len() < LONGEST {
+            let Ok((instruction, size)) = Instruction::decode(memory.get(next..)?) else {
+                break;
+            };
+            instructions.push((next as u32, instruction));
+            next += size;
+        }
+        let decoded: Vec<Instruction> = instructions.iter().map(|&(_, i)| i).collect();
+        use Instruction::*;
+        let fused = match decoded[..] {
+            [LoadImm(x, -4), Sub(s, s2, x2), LoadImm(x3, 4), Sub(x4, s3, x5), Load(0, x6), ..]
+                if x != 0
+                    && s != 0
+                    && x != s
+                    && [x2, x3, x4, x5, x6].iter().all(|&r| r == x)
+                    && [s2, s3].iter().all(|&r| r == s) =>
+            {
+                instructions.truncate(5);
+                Fused::Return { x, s }
+            }
+            [LoadImm(x, target), MoveIf(0, x2, c), ..] if x != 0 && x2 == x && c != 0 => {
+                instructions.truncate(2);
+                Fused::JumpIf { x, target, c }
+            }
+            [LoadImm(x, imm), Sub(a, b, x2), ..] if x != 0 && a != 0 && b != 0 && x2 == x => {
+                instructions.truncate(2);
+                Fused::LoadImmSub { x, imm, a, b }
+            }
+            _ => return None,
+        };
+        Some((fused, instructions))
+    }
+
+    /// Number of instructions in the sequence.
+    pub(crate) fn instructions(&self) -> u64 {
+        match self {
+            Fused::LoadImmSub { .. } | Fused::JumpIf { .. } => 2,
+            Fused::Return { .. } => 5,
+        }
+    }
+
+    /// Number of bytes of the sequence.
+    pub(crate) fn size(&self) -> u32 {
+        match self {
+            Fused::LoadImmSub { .. } => 8,
+            Fused::JumpIf { .. } => 8,
+            Fused::Return { .. } => MAX_FUSED_SIZE,
+        }
+    }
+}
@@ -0,0 +1,497 @@
+use crate::{MachineError, CONTROL_REGISTERS};
+use std::fmt;
+
+/// A decoded instruction. Register operands are kept as raw indices: they
+/// are checked against the machine registers when the instruction is
+/// executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// `1 reg_a reg_b reg_c`: if register reg_c contains a non-zero value,
+    /// copy the content of register reg_b into register reg_a; otherwise do
+    /// nothing.
+    MoveIf(u8, u8, u8),
+    /// `2 reg_a reg_b`: store the content of register reg_b into the memory
+    /// starting at address pointed by register reg_a using little-endian
+    /// representation.
+    Store(u8, u8),
+    /// `3 reg_a reg_b`: load the 32-bit content from memory at address
+    /// pointed by register reg_b into register reg_a using little-endian
+    /// representation.
+    Load(u8, u8),
+    /// `4 reg_a L H`: interpret H and L respectively as the high-order and
+    /// the low-order bytes of a 16-bit signed value, sign-extend it to 32
+    /// bits, and store it into register reg_a.
+    LoadImm(u8, i16),
+    /// `5 reg_a reg_b reg_c`: store the content of register reg_b minus the
+    /// content of register reg_c into register reg_a. Arithmetic wraps
+    /// around in case of overflow.
+    Sub(u8, u8, u8),
+    /// `6 reg_a`: output the character whose unicode value is stored in the
+    /// 8 low bits of register reg_a.
+    Out(u8),
+    /// `7`: exit the current program.
+    Exit,
+    /// `8 reg_a`: output the signed number stored in register reg_a in
+    /// decimal.
+    OutNumber(u8),
+    /// `9 reg_a`: read one byte from the input and store it into register
+    /// reg_a, or store -1 if the end of the input has been reached.
+    In(u8),
+    /// `10 reg_a`: read a signed decimal number from the input and store it
+    /// into register reg_a. Leading whitespace is skipped, and the character
+    /// ending the number is consumed.
+    InNumber(u8),
+    /// `11 reg_a reg_b reg_c`: store the content of register reg_b plus the
+    /// content of register reg_c into register reg_a. Arithmetic wraps
+    /// around in case of overflow.
+    Add(u8, u8, u8),
+    /// `12 reg_a reg_b reg_c`: store the content of register reg_b times the
+    /// content of register reg_c into register reg_a. Only the 32 low bits
+    /// of the product are kept, which is the same for signed and unsigned
+    /// values.
+    Mul(u8, u8, u8),
+    /// `13 reg_a reg_b reg_c`: store the quotient of the signed division of
+    /// register reg_b by register reg_c into register reg_a, rounded toward
+    /// zero. Dividing the smallest value by -1 wraps around to itself.
+    DivS(u8, u8, u8),
+    /// `14 reg_a reg_b reg_c`: store the quotient of the unsigned division of
+    /// register reg_b by register reg_c into register reg_a.
+    DivU(u8, u8, u8),
+    /// `15 reg_a reg_b reg_c`: store the remainder of the signed division of
+    /// register reg_b by register reg_c into register reg_a. It has the sign
+    /// of reg_b.
+    RemS(u8, u8, u8),
+    /// `16 reg_a reg_b reg_c`: store the remainder of the unsigned division
+    /// of register reg_b by register reg_c into register reg_a.
+    RemU(u8, u8, u8),
+    /// `17 reg_a reg_b reg_c`: store the bitwise and of registers reg_b and
+    /// reg_c into register reg_a.
+    And(u8, u8, u8),
+    /// `18 reg_a reg_b reg_c`: store the bitwise or of registers reg_b and
+    /// reg_c into register reg_a.
+    Or(u8, u8, u8),
+    /// `19 reg_a reg_b reg_c`: store the bitwise exclusive or of registers
+    /// reg_b and reg_c into register reg_a.
+    Xor(u8, u8, u8),
+    /// `20 reg_a reg_b`: store the bitwise complement of register reg_b into
+    /// register reg_a.
+    Not(u8, u8),
+    /// `21 reg_a reg_b reg_c`: shift register reg_b left by the number of
+    /// bits in register reg_c, and store the result into register reg_a.
+    /// Only the 5 low bits of the shift amount are used, as for the other
+    /// shifts.
+    Shl(u8, u8, u8),
+    /// `22 reg_a reg_b reg_c`: shift register reg_b right by the number of
+    /// bits in register reg_c, filling with zeroes, and store the result into
+    /// register reg_a.
+    Shr(u8, u8, u8),
+    /// `23 reg_a reg_b reg_c`: shift register reg_b right by the number of
+    /// bits in register reg_c, filling with copies of the sign bit, and store
+    /// the result into register reg_a.
+    Sar(u8, u8, u8),
+    /// `24 reg_a reg_b reg_c`: store 1 into register reg_a if register reg_b
+    /// is less than register reg_c as signed values, 0 otherwise.
+    Slt(u8, u8, u8),
+    /// `25 reg_a reg_b reg_c`: store 1 into register reg_a if register reg_b
+    /// is less than register reg_c as unsigned values, 0 otherwise.
+    SltU(u8, u8, u8),
+    /// `26 reg_a`: push the content of register reg_a onto the stack, by
+    /// decrementing the stack pointer r2 by 4 and storing the value at the
+    /// address it points to.
+    Push(u8),
+    /// `27 reg_a`: pop the value on top of the stack into register reg_a, by
+    /// loading the value pointed to by r2 and incrementing r2 by 4.
+    Pop(u8),
+    /// `28 L H`: push the address of the next instruction onto the stack,
+    /// and jump to the address given by the sign-extended 16-bit value
+    /// whose high-order and low-order bytes are H and L.
+    Call(i16),
+    /// `29`: pop the value on top of the stack into the IP, returning from
+    /// a call.
+    Ret,
+    /// `30 L H`: add the 16-bit signed value whose high-order and low-order
+    /// bytes are H and L to the IP, which then points after the
+    /// instruction. Arithmetic wraps around in case of overflow.
+    Jump(i16),
+    /// `31 reg_a L H`: if register reg_a contains zero, add the 16-bit signed
+    /// value whose high-order and low-order bytes are H and L to the IP, as
+    /// a jump does; otherwise do nothing.
+    BranchZero(u8, i16),
+    /// `32 reg_a L H`: if register reg_a contains a non-zero value, add the
+    /// 16-bit signed value whose high-order and low-order bytes are H and L
+    /// to the IP, as a jump does; otherwise do nothing.
+    BranchNonZero(u8, i16),
+    /// `33 reg_a ctl_b`: copy the content of control register ctl_b into
+    /// register reg_a.
+    ReadControl(u8, u8),
+    /// `34 ctl_a reg_b`: copy the content of register reg_b into control
+    /// register ctl_a.
+    WriteControl(u8, u8),
+    /// `35`: return from a trap handler, by jumping to the address held in
+    /// the [TRAP_IP](crate::TRAP_IP) control register.
+    ReturnFromTrap,
+    /// `36`: return from an interrupt handler, by jumping to the address
+    /// held in the [IRQ_IP](crate::IRQ_IP) control register.
+    ReturnFromInterrupt,
+}
+
+impl Instruction {
+    /// Decode the instruction found at the beginning of `bytes`, and return
+    /// it along with its size in bytes.
+    ///
+    /// An unknown opcode or control register gives
+    /// [MachineError::InvalidInstruction], and an
+    /// instruction which does not fit in `bytes` gives
+    /// [MachineError::InvalidMemoryAccess]. Addresses in those errors are
+    /// relative to the start of `bytes`.
+    pub fn decode(bytes: &[u8]) -> Result<(Instruction, usize), MachineError> {
+        let Some(&opcode) = bytes.first() else {
+            return Err(MachineError::InvalidMemoryAccess {
+                ip: 0,
+                opcode: None,
+                address: 0,
+                width: 1,
+                host: false,
+            });
+        };
+        let size =
+            Self::opcode_size(opcode).ok_or(MachineError::InvalidInstruction { ip: 0, opcode })?;
+        if bytes.len() < size {
+            return Err(MachineError::InvalidMemoryAccess {
+                ip: 0,
+                opcode: Some(opcode),
+                address: 0,
+                width: size as u32,
+                host: false,
+            });
+        }
+        let control = match opcode {
+            33 => bytes[2],
+            34 => bytes[1],
+            _ => 0,
+        };
+        if control as usize >= CONTROL_REGISTERS {
+            return Err(MachineError::InvalidInstruction { ip: 0, opcode });
+        }
+        let instruction = match opcode {
+            1 => Instruction::MoveIf(bytes[1], bytes[2], bytes[3]),
+            2 => Instruction::Store(bytes[1], bytes[2]),
+            3 => Instruction::Load(bytes[1], bytes[2]),
+            4 => Instruction::LoadImm(bytes[1], i16::from_le_bytes([bytes[2], bytes[3]])),
+            5 => Instruction::Sub(bytes[1], bytes[2], bytes[3]),
+            6 => Instruction::Out(bytes[1]),
+            7 => Instruction::Exit,
+            8 => Instruction::OutNumber(bytes[1]),
+            9 => Instruction::In(bytes[1]),
+            10 => Instruction::InNumber(bytes[1]),
+            11 => Instruction::Add(bytes[1], bytes[2], bytes[3]),
+            12 => Instruction::Mul(bytes[1], bytes[2], bytes[3]),
+            13 => Instruction::DivS(bytes[1], bytes[2], bytes[3]),
+            14 => Instruction::DivU(bytes[1], bytes[2], bytes[3]),
+            15 => Instruction::RemS(bytes[1], bytes[2], bytes[3]),
+            16 => Instruction::RemU(bytes[1], bytes[2], bytes[3]),
+            17 => Instruction::And(bytes[1], bytes[2], bytes[3]),
+            18 => Instruction::Or(bytes[1], bytes[2], bytes[3]),
+            19 => Instruction::Xor(bytes[1], bytes[2], bytes[3]),
+            21 => Instruction::Shl(bytes[1], bytes[2], bytes[3]),
+            22 => Instruction::Shr(bytes[1], bytes[2], bytes[3]),
+            23 => Instruction::Sar(bytes[1], bytes[2], bytes[3]),
+            24 => Instruction::Slt(bytes[1], bytes[2], bytes[3]),
+            25 => Instruction::SltU(bytes[1], bytes[2], bytes[3]),
+            20 => Instruction::Not(bytes[1], bytes[2]),
+            26 => Instruction::Push(bytes[1]),
+            27 => Instruction::Pop(bytes[1]),
+            28 => Instruction::Call(i16::from_le_bytes([bytes[1], bytes[2]])),
+            29 => Instruction::Ret,
+            30 => Instruction::Jump(i16::from_le_bytes([bytes[1], bytes[2]])),
+            31 => Instruction::BranchZero(bytes[1], i16::from_le_bytes([bytes[2], bytes[3]])),
+            32 => Instruction::BranchNonZero(bytes[1], i16::from_le_bytes([bytes[2], bytes[3]])),
+            33 => Instruction::ReadControl(bytes[1], bytes[2]),
+            34 => Instruction::WriteControl(bytes[1], bytes[2]),
+            35 => Instruction::ReturnFromTrap,
+            _ => Instruction::ReturnFromInterrupt,
+        };
+        Ok((instruction, size))
+    }
+
+    /// Size in bytes of the instructions using `opcode`, if it is valid.
+    pub fn opcode_size(opcode: u8) -> Option<usize> {
+        match opcode {
+            1 | 4 | 5 | 11..=19 | 21..=25 | 31 | 32 => Some(4),
+            2 | 3 | 20 | 28 | 30 | 33 | 34 => Some(3),
+            6 | 8 | 9 | 10 | 26 | 27 => Some(2),
+            7 | 29 | 35 | 36 => Some(1),
+            _ => None,
+        }
+    }
+
+    /// Opcode of the instruction.
+    pub fn opcode(&self) -> u8 {
+        match self {
+            Instruction::MoveIf(..) => 1,
+            Instruction::Store(..) => 2,
+            Instruction::Load(..) => 3,
+            Instruction::LoadImm(..) => 4,
+            Instruction::Sub(..) => 5,
+            Instruction::Out(..) => 6,
+            Instruction::Exit => 7,
+            Instruction::OutNumber(..) => 8,
+            Instruction::In(..) => 9,
+            Instruction::InNumber(..) => 10,
+            Instruction::Add(..) => 11,
+            Instruction::Mul(..) => 12,
+            Instruction::DivS(..) => 13,
+            Instruction::DivU(..) => 14,
+            Instruction::RemS(..) => 15,
+            Instruction::RemU(..) => 16,
+            Instruction::And(..) => 17,
+            Instruction::Or(..) => 18,
+            Instruction::Xor(..) => 19,
+            Instruction::Not(..) => 20,
+            Instruction::Shl(..) => 21,
+            Instruction::Shr(..) => 22,
+            Instruction::Sar(..) => 23,
+            Instruction::Slt(..) => 24,
+            Instruction::SltU(..) => 25,
+            Instruction::Push(..) => 26,
+            Instruction::Pop(..) => 27,
+            Instruction::Call(..) => 28,
+            Instruction::Ret => 29,
+            Instruction::Jump(..) => 30,
+            Instruction::BranchZero(..) => 31,
+            Instruction::BranchNonZero(..) => 32,
+            Instruction::ReadControl(..) => 33,
+            Instruction::WriteControl(..) => 34,
+            Instruction::ReturnFromTrap => 35,
+            Instruction::ReturnFromInterrupt => 36,
+        }
+    }
+
+    /// Binary representation of the instruction.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = vec![self.opcode()];
+        match *self {
+            Instruction::MoveIf(a, b, c)
+            | Instruction::Sub(a, b, c)
+            | Instruction::Add(a, b, c)
+            | Instruction::Mul(a, b, c)
+            | Instruction::DivS(a, b, c)
+            | Instruction::DivU(a, b, c)
+            | Instruction::RemS(a, b, c)
+            | Instruction::RemU(a, b, c)
+            | Instruction::And(a, b, c)
+            | Instruction::Or(a, b, c)
+            | Instruction::Xor(a, b, c)
+            | Instruction::Shl(a, b, c)
+            | Instruction::Shr(a, b, c)
+            | Instruction::Sar(a, b, c)
+            | Instruction::Slt(a, b, c)
+            | Instruction::SltU(a, b, c) => bytes.extend_from_slice(&[a, b, c]),
+            Instruction::Store(a, b)
+            | Instruction::Load(a, b)
+            | Instruction::Not(a, b)
+            | Instruction::ReadControl(a, b)
+            | Instruction::WriteControl(a, b) => bytes.extend_from_slice(&[a, b]),
+            Instruction::LoadImm(a, value)
+            | Instruction::BranchZero(a, value)
+            | Instruction::BranchNonZero(a, value) => {
+                bytes.push(a);
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            Instruction::Out(a)
+            | Instruction::OutNumber(a)
+            | Instruction::In(a)
+            | Instruction::InNumber(a)
+            | Instruction::Push(a)
+            | Instruction::Pop(a) => bytes.push(a),
+            Instruction::Call(value) | Instruction::Jump(value) => {
+                bytes.extend_from_slice(&value.to_le_bytes())
+            }
+            Instruction::Exit
+            | Instruction::Ret
+            | Instruction::ReturnFromTrap
+            | Instruction::ReturnFromInterrupt => (),
+        }
+        bytes
+    }
+
+    /// Size of the instruction in bytes.
+    pub fn size(&self) -> usize {
+        Self::opcode_size(self.opcode()).unwrap()
+    }
+
+    /// Highest register index used by the instruction, if any, including
+    /// the stack pointer r2 used by stack instructions.
+    pub fn max_register(&self) -> Option<u8> {
+        match *self {
+            Instruction::MoveIf(a, b, c)
+            | Instruction::Sub(a, b, c)
+            | Instruction::Add(a, b, c)
+            | Instruction::Mul(a, b, c)
+            | Instruction::DivS(a, b, c)
+            | Instruction::DivU(a, b, c)
+            | Instruction::RemS(a, b, c)
+            | Instruction::RemU(a, b, c)
+            | Instruction::And(a, b, c)
+            | Instruction::Or(a, b, c)
+            | Instruction::Xor(a, b, c)
+            | Instruction::Shl(a, b, c)
+            | Instruction::Shr(a, b, c)
+            | Instruction::Sar(a, b, c)
+            | Instruction::Slt(a, b, c)
+            | Instruction::SltU(a, b, c) => Some(a.max(b).max(c)),
+            Instruction::Store(a, b) | Instruction::Load(a, b) | Instruction::Not(a, b) => {
+                Some(a.max(b))
+            }
+            Instruction::LoadImm(a, _)
+            | Instruction::Out(a)
+            | Instruction::OutNumber(a)
+            | Instruction::In(a)
+            | Instruction::InNumber(a)
+            | Instruction::BranchZero(a, _)
+            | Instruction::BranchNonZero(a, _)
+            | Instruction::ReadControl(a, _)
+            | Instruction::WriteControl(_, a) => Some(a),
+            Instruction::Push(a) | Instruction::Pop(a) => Some(a.max(2)),
+            Instruction::Call(_) | Instruction::Ret => Some(2),
+            Instruction::Exit
+            | Instruction::Jump(_)
+            | Instruction::ReturnFromTrap
+            | Instruction::ReturnFromInterrupt => None,
+        }
+    }
+
+    /// Register written by the instruction, if any, apart from the stack
+    /// pointer updated by stack instructions and the IP updated by calls,
+    /// returns, jumps and branches. A move only writes it when its condition
+    /// holds.
+    pub fn destination(&self) -> Option<u8> {
+        match *self {
+            Instruction::MoveIf(a, ..)
+            | Instruction::Load(a, _)
+            | Instruction::LoadImm(a, _)
+            | Instruction::Sub(a, ..)
+            | Instruction::In(a)
+            | Instruction::InNumber(a)
+            | Instruction::Add(a, ..)
+            | Instruction::Mul(a, ..)
+            | Instruction::DivS(a, ..)
+            | Instruction::DivU(a, ..)
+            | Instruction::RemS(a, ..)
+            | Instruction::RemU(a, ..)
+            | Instruction::And(a, ..)
+            | Instruction::Or(a, ..)
+            | Instruction::Xor(a, ..)
+            | Instruction::Not(a, ..)
+            | Instruction::Shl(a, ..)
+            | Instruction::Shr(a, ..)
+            | Instruction::Sar(a, ..)
+            | Instruction::Slt(a, ..)
+            | Instruction::SltU(a, ..)
+            | Instruction::Pop(a)
+            | Instruction::ReadControl(a, _) => Some(a),
+            Instruction::Store(..)
+            | Instruction::Out(_)
+            | Instruction::Exit
+            | Instruction::OutNumber(_)
+            | Instruction::Push(_)
+            | Instruction::Call(_)
+            | Instruction::Ret
+            | Instruction::Jump(_)
+            | Instruction::BranchZero(..)
+            | Instruction::BranchNonZero(..)
+            | Instruction::WriteControl(..)
+            | Instruction::ReturnFromTrap
+            | Instruction::ReturnFromInterrupt => None,
+        }
+    }
+
+    /// Name of the instruction in the listings.
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::MoveIf(..) => "move",
+            Instruction::Store(..) => "store",
+            Instruction::Load(..) => "load",
+            Instruction::LoadImm(..) => "loadimm",
+            Instruction::Sub(..) => "sub",
+            Instruction::Out(..) => "out",
+            Instruction::Exit => "exit",
+            Instruction::OutNumber(..) => "out_number",
+            Instruction::In(..) => "in",
+            Instruction::InNumber(..) => "in_number",
+            Instruction::Add(..) => "add",
+            Instruction::Mul(..) => "mul",
+            Instruction::DivS(..) => "divs",
+            Instruction::DivU(..) => "divu",
+            Instruction::RemS(..) => "rems",
+            Instruction::RemU(..) => "remu",
+            Instruction::And(..) => "and",
+            Instruction::Or(..) => "or",
+            Instruction::Xor(..) => "xor",
+            Instruction::Not(..) => "not",
+            Instruction::Shl(..) => "shl",
+            Instruction::Shr(..) => "shr",
+            Instruction::Sar(..) => "sar",
+            Instruction::Slt(..) => "slt",
+            Instruction::SltU(..) => "sltu",
+            Instruction::Push(..) => "push",
+            Instruction::Pop(..) => "pop",
+            Instruction::Call(..) => "call",
+            Instruction::Ret => "ret",
+            Instruction::Jump(..) => "jmp",
+            Instruction::BranchZero(..) => "bz",
+            Instruction::BranchNonZero(..) => "bnz",
+            Instruction::ReadControl(..) => "rdctl",
+            Instruction::WriteControl(..) => "wrctl",
+            Instruction::ReturnFromTrap => "rtt",
+            Instruction::ReturnFromInterrupt => "reti",
+        }
+    }
+}
+
+/// Instructions are shown in the syntax of the `.dis` listings. The
+/// immediate of jumps and branches is their offset.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::MoveIf(a, b, c) => write!(f, "move r{} <- r{} if r{} != 0", a, b, c),
+            Instruction::Store(a, b) => write!(f, "store [r{}] <- r{}", a, b),
+            Instruction::Load(a, b) => write!(f, "load r{} <- [r{}]", a, b),
+            Instruction::LoadImm(a, value) => write!(f, "loadimm r{} <- #{}", a, value),
+            Instruction::Sub(a, b, c) => write!(f, "sub r{} <- r{} - r{}", a, b, c),
+            Instruction::Out(a) => write!(f, "out r{}", a),
+            Instruction::Exit => write!(f, "exit"),
+            Instruction::OutNumber(a) => write!(f, "out_number r{}", a),
+            Instruction::In(a) => write!(f, "in r{}", a),
+            Instruction::InNumber(a) => write!(f, "in_number r{}", a),
+            Instruction::Not(a, b) => write!(f, "not r{} <- r{}", a, b),
+            Instruction::Push(a) => write!(f, "push r{}", a),
+            Instruction::Pop(a) => write!(f, "pop r{}", a),
+            Instruction::Call(value) => write!(f, "call #{}", value),
+            Instruction::Ret => write!(f, "ret"),
+            Instruction::Jump(offset) => write!(f, "jmp #{}", offset),
+            Instruction::BranchZero(a, offset) => write!(f, "bz r{}, #{}", a, offset),
+            Instruction::BranchNonZero(a, offset) => write!(f, "bnz r{}, #{}", a, offset),
+            Instruction::ReadControl(a, b) => write!(f, "rdctl r{} <- c{}", a, b),
+            Instruction::WriteControl(a, b) => write!(f, "wrctl c{} <- r{}", a, b),
+            Instruction::ReturnFromTrap => write!(f, "rtt"),
+            Instruction::ReturnFromInterrupt => write!(f, "reti"),
+            Instruction::Add(a, b, c)
+            | Instruction::Mul(a, b, c)
+            | Instruction::DivS(a, b, c)
+            | Instruction::DivU(a, b, c)
+            | Instruction::RemS(a, b, c)
+            | Instruction::RemU(a, b, c)
+            | Instruction::And(a, b, c)
+            | Instruction::Or(a, b, c)
+            | Instruction::Xor(a, b, c)
+            | Instruction::Shl(a, b, c)
+            | Instruction::Shr(a, b, c)
+            | Instruction::Sar(a, b, c)
+            | Instruction::Slt(a, b, c)
+            | Instruction::SltU(a, b, c) => {
+                write!(f, "{} r{} <- r{}, r{}", self.mnemonic(), a, b, c)
+            }
+        }
+    }
+}
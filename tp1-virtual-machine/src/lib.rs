@@ -1,3 +1,35 @@
+mod assembler;
+mod backtrace;
+mod bus;
+mod cache;
+mod control;
+mod coverage;
+mod devices;
+mod disassembler;
+mod error;
+mod fusion;
+mod instruction;
 mod machine;
+mod profile;
+mod protection;
+mod snapshot;
+mod symbols;
+mod trace;
+mod translator;
 
+pub use assembler::*;
+pub use backtrace::{Backtrace, Frame};
+pub use bus::Device;
+pub use control::*;
+pub use coverage::*;
+pub use devices::*;
+pub use disassembler::*;
+pub use error::*;
+pub use instruction::*;
 pub use machine::*;
+pub use profile::*;
+pub use protection::*;
+pub use snapshot::*;
+pub use symbols::*;
+pub use trace::*;
+pub use translator::{translate, Code, Memory, Translated};
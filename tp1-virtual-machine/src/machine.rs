@@ -1,33 +1,72 @@
-use std::io::{self, Write};
+use crate::backtrace;
+use crate::bus::Bus;
+use crate::control::*;
+use crate::fusion::Fused;
+use crate::{
+    Coverage, Device, Frame, Instruction, MachineError, Permissions, Profile, Region, Snapshot,
+    Tracer,
+};
+use std::io::{self, Read, Write};
 
 const MEMORY_SIZE: usize = 4096;
 const NREGS: usize = 16;
 
 const IP: usize = 0;
+const SP: usize = 2;
 
-pub struct Machine {
-    // Memory
-    mem: [u8; MEMORY_SIZE],
-    // Registers
-    reg: [u32; NREGS]
+/// Geometry of a machine. The default is the historical machine, with
+/// 4096 bytes of memory and 16 registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MachineConfig {
+    /// Size of the memory in bytes, at most the 2^32 bytes which can be
+    /// addressed.
+    pub memory_size: usize,
+    /// Number of registers, between 1 and 256.
+    pub registers: usize,
 }
 
-#[derive(Debug)]
-pub enum MachineError {
-    // The program tried to access an invalid memory address.
-    InvalidMemoryAccess,
-    // The program tried to access an invalid register.
-    InvalidRegisterAccess,
-    // The program tried to execute an invalid instruction.
-    InvalidInstruction,
-    // The program failed to write to the output.
-    IOError(io::Error)
+impl Default for MachineConfig {
+    fn default() -> Self {
+        MachineConfig {
+            memory_size: MEMORY_SIZE,
+            registers: NREGS,
+        }
+    }
 }
 
-impl From<io::Error> for MachineError {
-    fn from(error: io::Error) -> Self {
-        MachineError::IOError(error)
-    }
+// Values changed by a recorded step, as they were before it.
+struct Undo {
+    reg: Vec<(usize, u32)>,
+    ctl: Vec<(usize, u32)>,
+    trapped: bool,
+    interrupted: bool,
+    memory: Vec<(u32, [u8; 4])>,
+}
+
+pub struct Machine {
+    // Memory, and devices mapped above it
+    bus: Bus,
+    // Registers
+    reg: Vec<u32>,
+    // Control registers
+    ctl: [u32; CONTROL_REGISTERS],
+    // Whether a trap handler is running
+    trapped: bool,
+    // Whether an interrupt handler is running
+    interrupted: bool,
+    // Size of the program image loaded at address 0
+    image_size: u32,
+    // Lowest address the stack may grow down to: the end of the program,
+    // or the start of the stack region
+    stack_limit: u32,
+    // Where executed instructions are traced, if anywhere
+    tracer: Option<Tracer>,
+    // Steps which can be undone, oldest first, when recording
+    undo: Option<Vec<Undo>>,
+    // Statistics of executed instructions, if profiling
+    profile: Option<Profile>,
+    // Executed instructions, if gathering coverage
+    coverage: Option<Coverage>,
 }
 
 impl Machine {
@@ -37,27 +76,348 @@ impl Machine {
     /// # Panics
     /// This function panics when `memory` is larger than the machine memory.
     pub fn new(memory: &[u8]) -> Self {
-        if memory.len()>MEMORY_SIZE {
-            panic!("The memory length is bigger than expected. It must not be bigger than {}", MEMORY_SIZE)
-        } else {
-            let mut mem = [0; MEMORY_SIZE];
-            mem[..memory.len()].copy_from_slice(memory);
-            let reg = [0; NREGS];
-            Machine {mem, reg}
+        match Self::with_config(memory, MachineConfig::default()) {
+            Ok(machine) => machine,
+            Err(_) => panic!(
+                "The memory length is bigger than expected. It must not be bigger than {}",
+                MEMORY_SIZE
+            ),
         }
     }
 
-    /// Run until the program terminates or until an error happens.
-    /// If output instructions are run, they print on `fd`.
-    pub fn run_on<T: Write>(&mut self, fd: &mut T) -> Result<(), MachineError> {
-        while !self.step_on(fd)? {}
+    /// Create a new machine with the given geometry in its reset state.
+    /// A memory larger than the address space gives
+    /// [MachineError::MemoryTooLarge]. The `memory` parameter will be
+    /// copied at the beginning of the machine memory, or
+    /// [MachineError::ImageTooLarge] is returned if it does not fit.
+    ///
+    /// The stack used by `push`, `pop`, `call` and `ret` lies between the
+    /// end of `memory` and the end of the machine memory: growing below the
+    /// former is a [MachineError::StackOverflow], and shrinking past the
+    /// latter a [MachineError::StackUnderflow].
+    ///
+    /// # Panics
+    /// This function panics when the number of registers is not between
+    /// 1 and 256, as register indices are encoded on one byte.
+    pub fn with_config(memory: &[u8], config: MachineConfig) -> Result<Self, MachineError> {
+        assert!(
+            (1..=256).contains(&config.registers),
+            "invalid number of registers"
+        );
+        if config.memory_size as u64 > 1 << 32 {
+            return Err(MachineError::MemoryTooLarge {
+                memory_size: config.memory_size,
+            });
+        }
+        if memory.len() > config.memory_size {
+            return Err(MachineError::ImageTooLarge {
+                size: memory.len(),
+                memory_size: config.memory_size,
+            });
+        }
+        let mut mem = vec![0; config.memory_size];
+        mem[..memory.len()].copy_from_slice(memory);
+        let reg = vec![0; config.registers];
+        let image_size = memory.len() as u32;
+        Ok(Machine {
+            bus: Bus::new(mem),
+            reg,
+            ctl: [0; CONTROL_REGISTERS],
+            trapped: false,
+            interrupted: false,
+            image_size,
+            stack_limit: image_size,
+            tracer: None,
+            undo: None,
+            profile: None,
+            coverage: None,
+        })
+    }
+
+    /// Geometry of this machine.
+    pub fn config(&self) -> MachineConfig {
+        MachineConfig {
+            memory_size: self.bus.ram().len(),
+            registers: self.reg.len(),
+        }
+    }
+
+    /// Map `device` into the address space at `base`, so that `load` and
+    /// `store` instructions can reach it. Its `tick` method is called after
+    /// every instruction.
+    ///
+    /// # Panics
+    /// This function panics when the device would overlap the memory or
+    /// another device, or extend past the end of the address space.
+    pub fn attach(&mut self, base: u32, device: Box<dyn Device>) {
+        self.bus.attach(base, device);
+    }
+
+    /// Take a snapshot of the registers, memory, protected regions and
+    /// devices.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            reg: self.reg.clone(),
+            ctl: self.ctl,
+            trapped: self.trapped,
+            interrupted: self.interrupted,
+            stack_limit: self.stack_limit,
+            image_size: self.image_size,
+            regions: self.bus.regions().to_vec(),
+            memory: self.bus.ram().to_vec(),
+            devices: self.bus.save(),
+        }
+    }
+
+    /// Put the machine back in the state saved in `snapshot`. The machine
+    /// must have the same geometry as the one the snapshot was taken from,
+    /// and the same kinds of devices attached in the same order, or
+    /// [MachineError::InvalidSnapshot] is returned and the machine is left
+    /// in an unspecified state.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), MachineError> {
+        if snapshot.config() != self.config() {
+            return Err(MachineError::InvalidSnapshot {
+                reason: format!(
+                    "taken from a machine with {} bytes of memory and {} registers",
+                    snapshot.memory.len(),
+                    snapshot.reg.len()
+                ),
+            });
+        }
+        self.bus.restore(&snapshot.devices)?;
+        self.reg.copy_from_slice(&snapshot.reg);
+        self.ctl = snapshot.ctl;
+        self.trapped = snapshot.trapped;
+        self.interrupted = snapshot.interrupted;
+        self.stack_limit = snapshot.stack_limit;
+        self.image_size = snapshot.image_size;
+        self.bus.set_regions(snapshot.regions.clone());
+        self.bus.ram_mut().copy_from_slice(&snapshot.memory);
+        // The recorded steps led to another state
+        if let Some(undo) = &mut self.undo {
+            undo.clear();
+        }
         Ok(())
     }
 
+    /// Add the statistics of every executed instruction to `profile`, or
+    /// stop profiling when `None` is given.
+    pub fn set_profile(&mut self, profile: Option<Profile>) {
+        self.profile = profile;
+    }
+
+    /// Statistics gathered since [set_profile](Machine::set_profile) was
+    /// called, if profiling.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Add every executed instruction to `coverage`, or stop gathering
+    /// coverage when `None` is given.
+    pub fn set_coverage(&mut self, coverage: Option<Coverage>) {
+        self.coverage = coverage;
+    }
+
+    /// Coverage gathered since [set_coverage](Machine::set_coverage) was
+    /// called, if any.
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Stop gathering coverage, and return the coverage gathered so far, so
+    /// that it can be extended by another run.
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// Record every step from now on, so that it can be undone with
+    /// [step_back](Machine::step_back), or stop recording and forget the
+    /// recorded steps. Only the registers and the memory are recorded: the
+    /// state of devices and the input or output of the program are not
+    /// rewound.
+    pub fn set_recording(&mut self, recording: bool) {
+        self.undo = recording.then(|| self.undo.take().unwrap_or_default());
+    }
+
+    /// Number of recorded steps which can be undone.
+    pub fn recorded_steps(&self) -> usize {
+        self.undo.as_ref().map_or(0, Vec::len)
+    }
+
+    /// Undo the last recorded step, putting the registers and memory back
+    /// to what they were before it. `false` is returned if there is no
+    /// step to undo.
+    pub fn step_back(&mut self) -> bool {
+        let Some(undo) = self.undo.as_mut().and_then(Vec::pop) else {
+            return false;
+        };
+        for (reg, value) in undo.reg {
+            self.reg[reg] = value;
+        }
+        for (ctl, value) in undo.ctl {
+            self.ctl[ctl] = value;
+        }
+        self.trapped = undo.trapped;
+        self.interrupted = undo.interrupted;
+        for (address, bytes) in undo.memory.into_iter().rev() {
+            let address = address as usize;
+            self.bus.ram_mut()[address..address + 4].copy_from_slice(&bytes);
+        }
+        true
+    }
+
+    /// Undo recorded steps until the IP is back to `addr`, so that the
+    /// instruction at this address is the next one executed. At least one
+    /// step is undone, so that repeated calls go to earlier and earlier
+    /// executions of the instruction. `false` is returned, with every
+    /// recorded step undone, if the instruction was not executed.
+    pub fn run_back_to(&mut self, addr: u32) -> bool {
+        while self.step_back() {
+            if self.reg[IP] == addr {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Decode every instruction once, and run it from its decoded form when
+    /// it is executed again, or decode it every time when `false` is given,
+    /// as by default. Instructions are decoded again once a `store`, or a
+    /// change of the memory from the host, overwrites them, so that
+    /// programs modifying their own code run the same either way.
+    ///
+    /// The sequences of instructions generated over and over by the
+    /// compiler, such as the ones adjusting the stack or returning from a
+    /// function, are also fused into single operations, which the `run`
+    /// methods use when no tracer, recording, profile or coverage needs to
+    /// see every instruction, and no interrupt can be taken. Devices still
+    /// tick once per fused instruction.
+    pub fn set_decode_cache(&mut self, enabled: bool) {
+        self.bus.set_decode_cache(enabled);
+    }
+
+    /// Restrict the accesses made within `region`. A denied access is a
+    /// [MachineError::ReadProtected], [MachineError::WriteProtected] or
+    /// [MachineError::ExecuteProtected] naming the region, and it can be
+    /// trapped. Regions do not restrict [set_memory](Machine::set_memory).
+    pub fn protect(&mut self, region: Region) {
+        self.bus.protect(region);
+    }
+
+    /// Make the program image loaded at the start of the memory a `text`
+    /// region, which can be read and executed but not written.
+    pub fn protect_image(&mut self) {
+        if self.image_size > 0 {
+            self.protect(Region::new("text", 0..self.image_size, Permissions::RX));
+        }
+    }
+
+    /// Make the last `size` bytes of the memory a `stack` region, which can
+    /// be read and written but not executed. Pushing below it is a
+    /// [MachineError::StackOverflow].
+    ///
+    /// # Panics
+    /// This function panics if the stack would overlap the program image.
+    pub fn protect_stack(&mut self, size: u32) {
+        let end = self.bus.ram().len() as u32;
+        let start = end
+            .checked_sub(size)
+            .filter(|&start| start >= self.image_size)
+            .expect("the stack overlaps the program image");
+        self.protect(Region::new("stack", start..end, Permissions::RW));
+        self.stack_limit = self.stack_limit.max(start);
+    }
+
+    /// Return addresses found on the stack, from r2 to the end of the
+    /// memory, innermost call first.
+    ///
+    /// A stack word is taken as a return address when it points right
+    /// after a `call`, or after a `loadimm r0 <- #function` such as the
+    /// ones ending the calling sequences of the example programs. Other
+    /// saved values may happen to look like return addresses as well.
+    /// Machines without r2 have no stack, hence no return address.
+    pub fn backtrace(&self) -> Vec<Frame> {
+        match self.reg.get(SP) {
+            Some(&sp) => backtrace::walk(self.bus.ram(), sp),
+            None => Vec::new(),
+        }
+    }
+
+    /// Regions restricting the accesses, in the order they were added.
+    pub fn regions(&self) -> &[Region] {
+        self.bus.regions()
+    }
+
+    /// Trace every executed instruction on `tracer`, or stop tracing
+    /// when `None` is given.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
     /// Run until the program terminates or until an error happens.
-    /// If output instructions are run, they print on standard output.
+    /// If input instructions are run, they read from `input`, and if
+    /// output instructions are run, they print on `output`.
+    /// If a tracer has been set with [set_tracer](Machine::set_tracer),
+    /// every executed instruction is traced.
+    pub fn run_io<R: Read, W: Write>(
+        &mut self,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<(), MachineError> {
+        while !self.step_fused(input, output, u64::MAX)?.0 {}
+        Ok(())
+    }
+
+    /// Similar to [run_io](Machine::run_io), with an empty input.
+    pub fn run_on<T: Write>(&mut self, fd: &mut T) -> Result<(), MachineError> {
+        self.run_io(&mut io::empty(), fd)
+    }
+
+    /// Similar to [run_io](Machine::run_io).
+    /// Input instructions read from standard input, and output
+    /// instructions print on standard output.
     pub fn run(&mut self) -> Result<(), MachineError> {
-        self.run_on(&mut io::stdout().lock())
+        self.run_io(&mut io::stdin(), &mut io::stdout().lock())
+    }
+
+    /// Similar to [run_io](Machine::run_io), but execute at most
+    /// `max_steps` instructions. If the program has not terminated by
+    /// then, [MachineError::StepLimitExceeded] is returned.
+    pub fn run_io_with_limit<R: Read, W: Write>(
+        &mut self,
+        input: &mut R,
+        output: &mut W,
+        max_steps: u64,
+    ) -> Result<(), MachineError> {
+        let mut steps = 0;
+        while steps < max_steps {
+            let (terminated, executed) = self.step_fused(input, output, max_steps - steps)?;
+            if terminated {
+                return Ok(());
+            }
+            steps += executed;
+        }
+        Err(MachineError::StepLimitExceeded {
+            steps: max_steps,
+            ip: self.reg[IP],
+        })
+    }
+
+    /// Similar to [run_io_with_limit](Machine::run_io_with_limit), with an
+    /// empty input.
+    pub fn run_on_with_limit<T: Write>(
+        &mut self,
+        fd: &mut T,
+        max_steps: u64,
+    ) -> Result<(), MachineError> {
+        self.run_io_with_limit(&mut io::empty(), fd, max_steps)
+    }
+
+    /// Similar to [run_io_with_limit](Machine::run_io_with_limit).
+    /// Input instructions read from standard input, and output
+    /// instructions print on standard output.
+    pub fn run_with_limit(&mut self, max_steps: u64) -> Result<(), MachineError> {
+        self.run_io_with_limit(&mut io::stdin(), &mut io::stdout().lock(), max_steps)
     }
 
     /// Execute the next instruction by doing the following steps:
@@ -65,39 +425,365 @@ impl Machine {
     ///   - increment the IP by the size of the instruction
     ///   - execute the decoded instruction
     ///
-    /// If output instructions are run, they print on `fd`.
+    /// If input instructions are run, they read from `input`, and if
+    /// output instructions are run, they print on `output`.
     /// If an error happens at either of those steps, an error is
-    /// returned.
+    /// returned, unless the program handles it with a trap handler (see
+    /// [TRAP_VECTOR]). Before the fetch, a pending interrupt may transfer
+    /// control to the interrupt handler (see [IRQ_VECTOR]), whose first
+    /// instruction is then executed. The step is recorded if recording was enabled
+    /// with [set_recording](Machine::set_recording).
     ///
     /// In case of success, `true` is returned if the program is
     /// terminated (upon encountering an exit instruction), or
     /// `false` if the execution must continue.
-    /// 
+    pub fn step_io<R: Read, W: Write>(
+        &mut self,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<bool, MachineError> {
+        let before = self.undo.is_some().then(|| {
+            self.bus.start_journal();
+            (self.reg.clone(), self.ctl, self.trapped, self.interrupted)
+        });
+        self.interrupt();
+        let ip = self.reg[IP];
+        let accesses = self.bus.accesses();
+        let result = match self.tracer.take() {
+            Some(mut tracer) => {
+                let result = self.traced_step(&mut tracer, input, output);
+                self.tracer = Some(tracer);
+                result
+            }
+            None => self.fetch_and_execute(input, output),
+        };
+        self.bus.tick();
+        let result = result.or_else(|error| self.trap(error));
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(ip);
+        }
+        if let Some(profile) = &mut self.profile {
+            let (reads, writes) = self.bus.accesses();
+            let opcode = self.bus.ram().get(ip as usize).copied();
+            profile.record(ip, opcode, reads - accesses.0, writes - accesses.1);
+        }
+        if let Some((reg, ctl, trapped, interrupted)) = before {
+            let undo = Undo {
+                reg: changed(&reg, &self.reg),
+                ctl: changed(&ctl, &self.ctl),
+                trapped,
+                interrupted,
+                memory: self.bus.take_journal(),
+            };
+            self.undo.as_mut().unwrap().push(undo);
+        }
+        result
+    }
+
+    /* Transfer control to the interrupt handler if an enabled interrupt is
+    pending, as described in the control module */
+    fn interrupt(&mut self) {
+        self.ctl[IRQ_PENDING as usize] = self.bus.pending();
+        let requested = self.ctl[IRQ_PENDING as usize] & self.ctl[IRQ_ENABLE as usize];
+        let vector = self.ctl[IRQ_VECTOR as usize];
+        if requested != 0 && vector != 0 && !self.interrupted && !self.trapped {
+            self.ctl[IRQ_IP as usize] = self.reg[IP];
+            self.reg[IP] = vector;
+            self.interrupted = true;
+        }
+    }
+
+    /* Transfer control to the trap handler if there is one, as described in
+    the control module, or give the error back */
+    fn trap(&mut self, error: MachineError) -> Result<bool, MachineError> {
+        let vector = self.ctl[TRAP_VECTOR as usize];
+        match error.trap() {
+            Some((ip, cause, value)) if vector != 0 && !self.trapped => {
+                self.ctl[TRAP_IP as usize] = ip;
+                self.ctl[TRAP_CAUSE as usize] = cause;
+                self.ctl[TRAP_VALUE as usize] = value;
+                self.reg[IP] = vector;
+                self.trapped = true;
+                Ok(false)
+            }
+            _ => Err(error),
+        }
+    }
+
+    /* Run the sequence fused at the IP if there is one of at most `budget`
+    instructions, and if nothing needs to see its instructions one by one,
+    or step otherwise. Whether the program is terminated is returned, along
+    with the number of instructions executed. */
+    fn step_fused<R: Read, W: Write>(
+        &mut self,
+        input: &mut R,
+        output: &mut W,
+        budget: u64,
+    ) -> Result<(bool, u64), MachineError> {
+        let ip = self.reg[IP];
+        match self.bus.fused(ip) {
+            Some(fused) if fused.instructions() <= budget && self.unobserved() => {
+                // Devices see the instructions go by as when stepping, the
+                // last one accessing memory after all but one tick
+                for _ in 1..fused.instructions() {
+                    self.bus.tick();
+                }
+                self.ctl[IRQ_PENDING as usize] = self.bus.pending();
+                let result = self.perform_fused(fused, ip);
+                self.bus.tick();
+                let terminated = result.or_else(|error| self.trap(error))?;
+                Ok((terminated, fused.instructions()))
+            }
+            _ => Ok((self.step_io(input, output)?, 1)),
+        }
+    }
+
+    /* Whether instructions can be executed without being traced, recorded
+    or counted, and without an interrupt being taken between them */
+    fn unobserved(&self) -> bool {
+        let interruptible = self.ctl[IRQ_ENABLE as usize] != 0
+            && self.ctl[IRQ_VECTOR as usize] != 0
+            && !self.interrupted
+            && !self.trapped;
+        self.tracer.is_none()
+            && self.undo.is_none()
+            && self.profile.is_none()
+            && self.coverage.is_none()
+            && !interruptible
+    }
+
+    /* Sequence starting at `ip` which can be fused, if all its instructions
+    may be executed */
+    fn fuse(&self, ip: u32) -> Option<Fused> {
+        let (fused, instructions) = Fused::detect(self.bus.ram(), ip)?;
+        instructions
+            .iter()
+            .all(|(address, instruction)| {
+                self.bus.check_execute(*address).is_ok()
+                    && self.check_registers(instruction).is_ok()
+            })
+            .then_some(fused)
+    }
+
+    /* Execute the sequence fused at `ip`, leaving the registers as its
+    instructions would */
+    fn perform_fused(&mut self, fused: Fused, ip: u32) -> Result<bool, MachineError> {
+        let next = ip + fused.size();
+        match fused {
+            Fused::LoadImmSub { x, imm, a, b } => {
+                self.reg[x as usize] = imm as u32;
+                self.reg[a as usize] = self.reg[b as usize].wrapping_sub(self.reg[x as usize]);
+                self.reg[IP] = next;
+            }
+            Fused::JumpIf { x, target, c } => {
+                self.reg[x as usize] = target as u32;
+                self.reg[IP] = if self.reg[c as usize] != 0 {
+                    self.reg[x as usize]
+                } else {
+                    next
+                };
+            }
+            Fused::Return { x, s } => {
+                let (x, s) = (x as usize, s as usize);
+                self.reg[s] = self.reg[s].wrapping_add(4);
+                self.reg[x] = self.reg[s].wrapping_sub(4);
+                self.reg[IP] = next;
+                // Faults are attributed to the final load
+                let load = Instruction::Load(0, 0);
+                self.reg[IP] = self
+                    .bus
+                    .read32(self.reg[x])
+                    .map_err(|e| e.in_instruction(next - load.size() as u32, load.opcode()))?;
+            }
+        }
+        Ok(false)
+    }
+
+    /// Similar to [step_io](Machine::step_io), with an empty input.
     pub fn step_on<T: Write>(&mut self, fd: &mut T) -> Result<bool, MachineError> {
-        /*step_on(): takes a Write-implementing descriptor (for the out and out number 
-        instructions), and execute just one instruction */
-        let adr = self.reg[IP];
-        if adr as usize >= MEMORY_SIZE {
-            return Err(MachineError::InvalidMemoryAccess);
-        }
-        let inst = self.mem[adr as usize];
-        match inst {
-            1 => self.move_if(),
-            2 => self.store(),
-            3 => self.load(),
-            4 => self.loadimm(),
-            5 => self.sub(),
-            6 => self.out(fd),
-            7 => self.exit(),
-            8 => self.out_number(fd),
-            _ => Err(MachineError::InvalidInstruction)
-        }  
-    }
-
-    /// Similar to [step_on](Machine::step_on).
-    /// If output instructions are run, they print on standard output.
+        self.step_io(&mut io::empty(), fd)
+    }
+
+    fn fetch_and_execute<R: Read, W: Write>(
+        &mut self,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<bool, MachineError> {
+        let ip = self.reg[IP];
+        // A cached instruction has been checked when it was decoded
+        if let Some((instruction, size)) = self.bus.decoded(ip) {
+            self.reg[IP] = ip + size;
+            return self
+                .perform(instruction, input, output)
+                .map_err(|e| e.in_instruction(ip, instruction.opcode()));
+        }
+        let (instruction, size) = self.fetch()?;
+        /* Registers are checked before the IP is updated, so that a faulty
+        instruction leaves the machine untouched */
+        self.check_registers(&instruction)
+            .map_err(|e| e.in_instruction(ip, instruction.opcode()))?;
+        self.bus.cache(ip, instruction, size);
+        if self.bus.decoded(ip).is_some() {
+            if let Some(fused) = self.fuse(ip) {
+                self.bus.cache_fused(ip, fused);
+            }
+        }
+        self.reg[IP] += size as u32;
+        self.execute_io(instruction, input, output)
+    }
+
+    fn traced_step<R: Read, W: Write>(
+        &mut self,
+        tracer: &mut Tracer,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<bool, MachineError> {
+        let ip = self.reg[IP];
+        let before = self.reg.clone();
+        let instruction = self.fetch().ok().map(|(instruction, _)| instruction);
+        let result = self.fetch_and_execute(input, output);
+        let stored = match (instruction, &result) {
+            (Some(Instruction::Store(a, b)), Ok(_)) => {
+                Some((before[a as usize], before[b as usize]))
+            }
+            (Some(Instruction::Push(a)), Ok(_)) => Some((self.reg[SP], before[a as usize])),
+            (Some(Instruction::Call(_)), Ok(_)) => Some((
+                self.reg[SP],
+                ip.wrapping_add(Instruction::Call(0).size() as u32),
+            )),
+            _ => None,
+        };
+        let error = result.as_ref().err();
+        tracer.record(ip, instruction, &before, &self.reg, stored, error)?;
+        result
+    }
+
+    /// Similar to [step_io](Machine::step_io).
+    /// Input instructions read from standard input, and output
+    /// instructions print on standard output.
     pub fn step(&mut self) -> Result<bool, MachineError> {
-        self.step_on(&mut io::stdout().lock())
+        self.step_io(&mut io::stdin(), &mut io::stdout().lock())
+    }
+
+    /// Execute an already decoded instruction. The IP is expected to point
+    /// after the instruction already, as it does when called from
+    /// [step_io](Machine::step_io).
+    ///
+    /// If input instructions are run, they read from `input`, and if
+    /// output instructions are run, they print on `output`.
+    /// `true` is returned if the program is terminated, `false` otherwise.
+    pub fn execute_io<R: Read, W: Write>(
+        &mut self,
+        instruction: Instruction,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<bool, MachineError> {
+        /* Faults are attributed to the instruction, which is located just
+        before the IP */
+        let ip = self.reg[IP].wrapping_sub(instruction.size() as u32);
+        self.dispatch(instruction, input, output)
+            .map_err(|e| e.in_instruction(ip, instruction.opcode()))
+    }
+
+    /// Similar to [execute_io](Machine::execute_io), with an empty input.
+    pub fn execute_on<T: Write>(
+        &mut self,
+        instruction: Instruction,
+        fd: &mut T,
+    ) -> Result<bool, MachineError> {
+        self.execute_io(instruction, &mut io::empty(), fd)
+    }
+
+    fn dispatch<R: Read, W: Write>(
+        &mut self,
+        instruction: Instruction,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<bool, MachineError> {
+        self.check_registers(&instruction)?;
+        self.perform(instruction, input, output)
+    }
+
+    /* Execute an instruction whose registers have been checked */
+    fn perform<R: Read, W: Write>(
+        &mut self,
+        instruction: Instruction,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<bool, MachineError> {
+        match instruction {
+            Instruction::MoveIf(a, b, c) => self.move_if(a as usize, b as usize, c as usize),
+            Instruction::Store(a, b) => self.store(a as usize, b as usize),
+            Instruction::Load(a, b) => self.load(a as usize, b as usize),
+            Instruction::LoadImm(a, value) => self.loadimm(a as usize, value),
+            Instruction::Sub(a, b, c) => self.sub(a as usize, b as usize, c as usize),
+            Instruction::Out(a) => self.out(a as usize, output),
+            Instruction::Exit => Ok(true),
+            Instruction::OutNumber(a) => self.out_number(a as usize, output),
+            Instruction::In(a) => self.input(a as usize, input),
+            Instruction::InNumber(a) => self.in_number(a as usize, input),
+            Instruction::Add(a, b, c) => self.arith(a, b, c, u32::wrapping_add),
+            Instruction::Mul(a, b, c) => self.arith(a, b, c, u32::wrapping_mul),
+            Instruction::DivS(a, b, c) => {
+                self.divide(a, b, c, |x, y| (x as i32).wrapping_div(y as i32) as u32)
+            }
+            Instruction::DivU(a, b, c) => self.divide(a, b, c, |x, y| x / y),
+            Instruction::RemS(a, b, c) => {
+                self.divide(a, b, c, |x, y| (x as i32).wrapping_rem(y as i32) as u32)
+            }
+            Instruction::RemU(a, b, c) => self.divide(a, b, c, |x, y| x % y),
+            Instruction::And(a, b, c) => self.arith(a, b, c, |x, y| x & y),
+            Instruction::Or(a, b, c) => self.arith(a, b, c, |x, y| x | y),
+            Instruction::Xor(a, b, c) => self.arith(a, b, c, |x, y| x ^ y),
+            Instruction::Not(a, b) => self.arith(a, b, b, |x, _| !x),
+            Instruction::Shl(a, b, c) => self.arith(a, b, c, u32::wrapping_shl),
+            Instruction::Shr(a, b, c) => self.arith(a, b, c, u32::wrapping_shr),
+            Instruction::Sar(a, b, c) => {
+                self.arith(a, b, c, |x, y| (x as i32).wrapping_shr(y) as u32)
+            }
+            Instruction::Slt(a, b, c) => {
+                self.arith(a, b, c, |x, y| ((x as i32) < (y as i32)) as u32)
+            }
+            Instruction::SltU(a, b, c) => self.arith(a, b, c, |x, y| (x < y) as u32),
+            Instruction::Push(a) => self.push(self.reg[a as usize]),
+            Instruction::Pop(a) => self.pop(a as usize),
+            Instruction::Call(target) => {
+                self.push(self.reg[IP])?;
+                self.reg[IP] = target as u32;
+                Ok(false)
+            }
+            Instruction::Ret => self.pop(IP),
+            Instruction::Jump(offset) => self.jump(offset),
+            Instruction::BranchZero(a, offset) if self.reg[a as usize] == 0 => self.jump(offset),
+            Instruction::BranchNonZero(a, offset) if self.reg[a as usize] != 0 => self.jump(offset),
+            Instruction::BranchZero(..) | Instruction::BranchNonZero(..) => Ok(false),
+            Instruction::ReadControl(a, b) => {
+                self.reg[a as usize] = self.ctl[b as usize];
+                Ok(false)
+            }
+            Instruction::WriteControl(IRQ_PENDING, _) => Ok(false),
+            Instruction::WriteControl(a, b) => {
+                self.ctl[a as usize] = self.reg[b as usize];
+                Ok(false)
+            }
+            Instruction::ReturnFromTrap => {
+                self.reg[IP] = self.ctl[TRAP_IP as usize];
+                self.trapped = false;
+                Ok(false)
+            }
+            Instruction::ReturnFromInterrupt => {
+                self.reg[IP] = self.ctl[IRQ_IP as usize];
+                self.interrupted = false;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Similar to [execute_io](Machine::execute_io).
+    /// Input instructions read from standard input, and output
+    /// instructions print on standard output.
+    pub fn execute(&mut self, instruction: Instruction) -> Result<bool, MachineError> {
+        self.execute_io(instruction, &mut io::stdin(), &mut io::stdout().lock())
     }
 
     /// Reference onto the machine current set of registers.
@@ -107,178 +793,225 @@ impl Machine {
 
     /// Sets a register to the given value.
     pub fn set_reg(&mut self, reg: usize, value: u32) -> Result<(), MachineError> {
-        if reg >= NREGS {
-            return Err(MachineError::InvalidRegisterAccess);
+        if reg >= self.reg.len() {
+            return Err(MachineError::InvalidRegisterAccess {
+                ip: self.reg[IP],
+                opcode: None,
+                register: reg,
+                host: true,
+            });
         }
         self.reg[reg] = value;
         Ok(())
     }
 
-    /// Reference onto the machine current memory.
+    /// Reference onto the machine current set of control registers.
+    pub fn controls(&self) -> &[u32] {
+        &self.ctl
+    }
+
+    /// Sets a control register to the given value.
+    ///
+    /// # Panics
+    /// This function panics when `reg` is not a valid control register.
+    pub fn set_control(&mut self, reg: u8, value: u32) {
+        self.ctl[reg as usize] = value;
+    }
+
+    /// Reference onto the machine current memory. Devices are not part of
+    /// it.
     pub fn memory(&self) -> &[u8] {
-        &self.mem
-    }
-
-    /*move if
-    1 reg_a reg_b reg_c: if register reg_c contains a non-zero value, copy the content of 
-    register reg_b into register reg_a; otherwise do nothing. */
-    fn move_if(&mut self) -> Result<bool, MachineError> {
-        let adr = &mut self.reg[IP];
-        if (*adr as usize + 3) >= MEMORY_SIZE {
-            return Err(MachineError::InvalidMemoryAccess);
-        }
-        let reg_a = self.mem[*adr as usize + 1] as usize;
-        let reg_b = self.mem[*adr as usize + 2] as usize;
-        let reg_c = self.mem[*adr as usize + 3] as usize;
-        if reg_a >= NREGS || reg_b >= NREGS || reg_c >= NREGS {
-            return Err(MachineError::InvalidRegisterAccess);
-        }
-        /* I need to update adr here so that adr goes out of scope and can access other 
-        registers directly */
-        *adr += 4; 
+        self.bus.ram()
+    }
+
+    /// Copies `bytes` into the memory, starting at `address`.
+    pub fn set_memory(&mut self, address: usize, bytes: &[u8]) -> Result<(), MachineError> {
+        match address.checked_add(bytes.len()) {
+            Some(end) if end <= self.bus.ram().len() => {
+                self.bus.ram_mut()[address..end].copy_from_slice(bytes);
+                Ok(())
+            }
+            _ => Err(MachineError::InvalidMemoryAccess {
+                ip: self.reg[IP],
+                opcode: None,
+                address: address as u32,
+                width: bytes.len() as u32,
+                host: true,
+            }),
+        }
+    }
+
+    /* Decode the instruction located at IP */
+    fn fetch(&self) -> Result<(Instruction, usize), MachineError> {
+        let adr = self.reg[IP];
+        self.bus.check_execute(adr)?;
+        Instruction::decode(self.bus.ram().get(adr as usize..).unwrap_or(&[]))
+            .map_err(|e| e.relocate(adr))
+    }
+
+    fn check_registers(&self, instruction: &Instruction) -> Result<(), MachineError> {
+        match instruction.max_register() {
+            Some(reg) if reg as usize >= self.reg.len() => {
+                Err(MachineError::InvalidRegisterAccess {
+                    ip: 0,
+                    opcode: None,
+                    register: reg as usize,
+                    host: false,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn move_if(&mut self, reg_a: usize, reg_b: usize, reg_c: usize) -> Result<bool, MachineError> {
         if self.reg[reg_c] != 0 {
             self.reg[reg_a] = self.reg[reg_b];
         }
         Ok(false)
     }
-    /*store
-    2 reg_a reg_b: store the content of register reg_b into the memory starting at address 
-    pointed by register reg_a using little-endian representation. */
-    fn store(&mut self) -> Result<bool, MachineError>{
-        let adr = &mut self.reg[IP];
-        if (*adr as usize + 2)  >= MEMORY_SIZE {
-            return Err(MachineError::InvalidMemoryAccess);
-        }
-        let reg_a = self.mem[*adr as usize + 1] as usize;
-        let reg_b = self.mem[*adr as usize + 2] as usize;
-        if reg_a >= NREGS || reg_b >= NREGS {
-            return Err(MachineError::InvalidRegisterAccess);
-        }
-        /* I need to update adr here so that adr goes out of scope and can access other 
-        registers directly */
-        *adr += 3; 
-        if ((self.reg[reg_a] as usize)+3) >= MEMORY_SIZE {
-            Err(MachineError::InvalidMemoryAccess)
-        } else {
-            let src = self.reg[reg_b].to_le_bytes();
-            let dst = &mut self.mem[self.reg[reg_a] as usize..(self.reg[reg_a] + 4) as usize];
-            dst.copy_from_slice(&src);
-            Ok(false)
-        }
-    }
-
-    /* load
-    3 reg_a reg_b: load the 32-bit content from memory at address pointed by register reg_b
-    into register reg_a using little-endian representation. */
-    fn load(&mut self) -> Result<bool, MachineError> {
-        let adr = &mut self.reg[IP];
-        if (*adr as usize + 2)  >= MEMORY_SIZE {
-            return Err(MachineError::InvalidMemoryAccess);
-        }
-        let reg_a = self.mem[*adr as usize + 1] as usize;
-        let reg_b = self.mem[*adr as usize + 2] as usize;
-        if reg_b >= NREGS || reg_a >= NREGS {
-            return Err(MachineError::InvalidRegisterAccess);
-        }
-        /* I need to update adr here so that adr goes out of scope and can access other 
-        registers directly */
-        *adr += 3;
-        if ((self.reg[reg_b] as usize)+3) >= MEMORY_SIZE {
-            return Err(MachineError::InvalidMemoryAccess);
-        }
-        let src = &self.mem[self.reg[reg_b] as usize..(self.reg[reg_b]+4) as usize];
-        self.reg[reg_a] = u32::from_le_bytes(src.try_into().unwrap());
+
+    fn store(&mut self, reg_a: usize, reg_b: usize) -> Result<bool, MachineError> {
+        self.bus.write32(self.reg[reg_a], self.reg[reg_b])?;
         Ok(false)
     }
 
-    /*loadimm
-    4 reg_a L H: interpret H and L respectively as the high-order and the low-order bytes of
-    a 16-bit signed value, sign-extend it to 32 bits, and store it into register reg_a. */
-    fn loadimm(&mut self) -> Result<bool, MachineError> {
-        let adr = &mut self.reg[IP];
-        if (*adr as usize + 3)  >= MEMORY_SIZE {
-            return Err(MachineError::InvalidMemoryAccess);
-        }
-        let reg_a = self.mem[*adr as usize + 1] as usize;
-        let l = self.mem[*adr as usize + 2];
-        let h = self.mem[*adr as usize + 3];
-        if reg_a >= NREGS {
-            return Err(MachineError::InvalidRegisterAccess);
-        }
-        /* I need to update adr here so that adr goes out of scope and can access other 
-        registers directly */
-        *adr += 4;
-        let value = i16::from_le_bytes([l,h]);
+    fn load(&mut self, reg_a: usize, reg_b: usize) -> Result<bool, MachineError> {
+        self.reg[reg_a] = self.bus.read32(self.reg[reg_b])?;
+        Ok(false)
+    }
+
+    fn loadimm(&mut self, reg_a: usize, value: i16) -> Result<bool, MachineError> {
         self.reg[reg_a] = value as u32;
         Ok(false)
     }
 
-    /*sub
-    5 reg_a reg_b reg_c: store the content of register reg_b minus the content of register reg_c into 
-    register reg_a
-    Arithmetic wraps around in case of overflow. For example, 0 - 1 returns 0xffffffff, 
-    and 0 - 0xffffffff returns 1. */
-    fn sub(&mut self) -> Result<bool, MachineError> {
-        let adr = &mut self.reg[IP];
-        if (*adr as usize + 3)  >= MEMORY_SIZE {
-            return Err(MachineError::InvalidMemoryAccess);
-        }
-        let reg_a = self.mem[*adr as usize + 1] as usize;
-        let reg_b = self.mem[*adr as usize + 2] as usize;
-        let reg_c = self.mem[*adr as usize + 3] as usize;
-        if reg_a >= NREGS || reg_b >= NREGS || reg_c >= NREGS {
-            return Err(MachineError::InvalidRegisterAccess);
-        }
-        /* I need to update adr here so that adr goes out of scope and can access other 
-        registers directly */
-        *adr += 4;
+    /* For example, 0 - 1 returns 0xffffffff, and 0 - 0xffffffff returns 1. */
+    fn sub(&mut self, reg_a: usize, reg_b: usize, reg_c: usize) -> Result<bool, MachineError> {
         self.reg[reg_a] = self.reg[reg_b].wrapping_sub(self.reg[reg_c]);
         Ok(false)
     }
 
-    /*out
-    6 reg_a: output the character whose unicode value is stored in the 8 low bits of register reg_a. */
-    fn out<T: Write>(&mut self, fd: &mut T) -> Result<bool, MachineError> {
-        let adr = &mut self.reg[IP];
-        if (*adr as usize + 1) >= MEMORY_SIZE {
-            return Err(MachineError::InvalidMemoryAccess);
+    /* Store `op` applied to registers reg_b and reg_c into register reg_a */
+    fn arith(
+        &mut self,
+        reg_a: u8,
+        reg_b: u8,
+        reg_c: u8,
+        op: fn(u32, u32) -> u32,
+    ) -> Result<bool, MachineError> {
+        self.reg[reg_a as usize] = op(self.reg[reg_b as usize], self.reg[reg_c as usize]);
+        Ok(false)
+    }
+
+    /* Same as arith, for divisions whose divisor reg_c must not be zero */
+    fn divide(
+        &mut self,
+        reg_a: u8,
+        reg_b: u8,
+        reg_c: u8,
+        op: fn(u32, u32) -> u32,
+    ) -> Result<bool, MachineError> {
+        if self.reg[reg_c as usize] == 0 {
+            return Err(MachineError::DivisionByZero { ip: 0 });
+        }
+        self.arith(reg_a, reg_b, reg_c, op)
+    }
+
+    /* The offset is relative to the IP, already pointing after the jump */
+    fn jump(&mut self, offset: i16) -> Result<bool, MachineError> {
+        self.reg[IP] = self.reg[IP].wrapping_add(offset as u32);
+        Ok(false)
+    }
+
+    fn push(&mut self, value: u32) -> Result<bool, MachineError> {
+        let sp = self.reg[SP];
+        if sp < self.stack_limit.saturating_add(4) {
+            return Err(MachineError::StackOverflow { ip: 0, sp });
         }
-        let reg_a = self.mem[*adr as usize + 1] as usize;
-        if reg_a >= NREGS {
-            return Err(MachineError::InvalidRegisterAccess);
+        self.bus.write32(sp - 4, value)?;
+        self.reg[SP] = sp - 4;
+        Ok(false)
+    }
+
+    /* The stack pointer is updated before reg_a is written, so that
+    `pop r2` loads the popped value into r2 */
+    fn pop(&mut self, reg_a: usize) -> Result<bool, MachineError> {
+        let sp = self.reg[SP];
+        if sp as usize + 4 > self.bus.ram().len() {
+            return Err(MachineError::StackUnderflow { ip: 0, sp });
         }
-        /* I need to update adr here so that adr goes out of scope and can access other 
-        registers directly */
-        *adr += 2;
+        let value = self.bus.read32(sp)?;
+        self.reg[SP] = sp + 4;
+        self.reg[reg_a] = value;
+        Ok(false)
+    }
+
+    fn out<T: Write>(&mut self, reg_a: usize, fd: &mut T) -> Result<bool, MachineError> {
         let c: char = self.reg[reg_a] as u8 as char;
-        write!(fd, "{}", c).map_err(|e| MachineError::IOError(e))?;
+        write!(fd, "{}", c).map_err(MachineError::IOError)?;
         Ok(false)
     }
 
-    /*exit
-    7: exit the current program */
-    fn exit(&mut self) -> Result<bool, MachineError> {
-        let adr = &mut self.reg[IP];
-        *adr += 1;
-        Ok(true)
+    fn out_number<T: Write>(&mut self, reg_a: usize, fd: &mut T) -> Result<bool, MachineError> {
+        write!(fd, "{}", self.reg[reg_a] as i32).map_err(MachineError::IOError)?;
+        Ok(false)
+    }
+
+    fn input<T: Read>(&mut self, reg_a: usize, fd: &mut T) -> Result<bool, MachineError> {
+        self.reg[reg_a] = match read_byte(fd)? {
+            Some(byte) => byte as u32,
+            None => u32::MAX,
+        };
+        Ok(false)
     }
 
-    /*out number
-    8 reg_a: output the signed number stored in register reg_a in decimal.*/
-    fn out_number<T: Write>(&mut self, fd: &mut T) -> Result<bool, MachineError> {
-        let adr = &mut self.reg[IP];
-        if (*adr as usize + 1) >= MEMORY_SIZE {
-            return Err(MachineError::InvalidMemoryAccess);
+    /* The number wraps around like arithmetic does, and the byte following
+    its digits is consumed, as there is no way to put it back. */
+    fn in_number<T: Read>(&mut self, reg_a: usize, fd: &mut T) -> Result<bool, MachineError> {
+        let mut byte = read_byte(fd)?;
+        while byte.is_some_and(|b| b.is_ascii_whitespace()) {
+            byte = read_byte(fd)?;
+        }
+        let negative = byte == Some(b'-');
+        if matches!(byte, Some(b'-' | b'+')) {
+            byte = read_byte(fd)?;
         }
-        let reg_a = self.mem[*adr as usize + 1] as usize;
-        if reg_a >= NREGS {
-            return Err(MachineError::InvalidRegisterAccess);
+        let mut value: Option<u32> = None;
+        while let Some(digit @ b'0'..=b'9') = byte {
+            let digit = (digit - b'0') as u32;
+            value = Some(value.unwrap_or(0).wrapping_mul(10).wrapping_add(digit));
+            byte = read_byte(fd)?;
         }
-        /* I need to update adr here so that adr goes out of scope and can access other 
-        registers directly */
-        *adr += 2;
-        write!(fd, "{}", self.reg[reg_a] as i32).map_err(|e| MachineError::IOError(e))?;
+        let value = value.ok_or(MachineError::InvalidNumber { ip: 0 })?;
+        self.reg[reg_a] = if negative {
+            value.wrapping_neg()
+        } else {
+            value
+        };
         Ok(false)
     }
+}
+
+// Indices and previous values of the elements which differ in `after`.
+fn changed(before: &[u32], after: &[u32]) -> Vec<(usize, u32)> {
+    before
+        .iter()
+        .zip(after)
+        .enumerate()
+        .filter(|(_, (b, a))| b != a)
+        .map(|(i, (&b, _))| (i, b))
+        .collect()
+}
 
+/* Read one byte, or None at the end of the input */
+fn read_byte<T: Read>(fd: &mut T) -> Result<Option<u8>, MachineError> {
+    let mut byte = [0];
+    loop {
+        match fd.read(&mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(MachineError::IOError(e)),
+        }
+    }
 }
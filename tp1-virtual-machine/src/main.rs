@@ -1,19 +1,309 @@
-use interpreter::{Machine, MachineError};
-use std::fs::File;
-use std::io::Read;
+use interpreter::{
+    disassemble, Backtrace, Console, Framebuffer, Machine, MachineConfig, MachineError, Profile,
+    Snapshot, Symbols, Timer, Tracer, CONSOLE_BASE, FRAMEBUFFER_BASE, TIMER_BASE,
+};
+use std::io;
+use std::process::ExitCode;
 
-fn main() -> Result<(), MachineError> {
-    // Take a filename as argument on the command line
-    let filename = std::env::args().nth(1).unwrap();
+const USAGE: &str = "usage: tp-rust-2 [--trace] [--symbols <program.dis>] [--max-steps <n>] \
+                     [--memory-size <bytes>] [--frames <frames.bin>] [--profile <report.txt>] \
+                     [--save-on-exit <state.snap>] \
+                     ([--protect] [--stack-size <bytes>] <program.bin> | --resume <state.snap>)";
+
+struct Options {
+    // Program to run, or snapshot to resume from
+    filename: String,
+    // Whether `filename` is a snapshot
+    resume: bool,
+    // Trace executed instructions on standard error
+    trace: bool,
+    // Listing whose labels annotate the trace and the profile
+    symbols: Option<String>,
+    // Stop the program after this many instructions
+    max_steps: Option<u64>,
+    // Size of the machine memory
+    memory_size: Option<usize>,
+    // File receiving the images of the framebuffer
+    frames: Option<String>,
+    // File receiving a snapshot of the machine once it stops
+    save_on_exit: Option<String>,
+    // File receiving the profile of the program once it stops
+    profile: Option<String>,
+    // Forbid writes to the program image
+    protect: bool,
+    // Size of the stack region at the end of the memory
+    stack_size: Option<u32>,
+}
+
+// Parse the command line, or return None if it is invalid.
+fn parse_args() -> Option<Options> {
+    let mut args = std::env::args().skip(1);
+    let mut filename = None;
+    let mut trace = false;
+    let mut symbols = None;
+    let mut max_steps = None;
+    let mut memory_size = None;
+    let mut frames = None;
+    let mut resume = None;
+    let mut save_on_exit = None;
+    let mut profile = None;
+    let mut protect = false;
+    let mut stack_size = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--trace" => trace = true,
+            "--symbols" => symbols = Some(args.next()?),
+            "--max-steps" => max_steps = Some(args.next()?.parse().ok()?),
+            "--memory-size" => memory_size = Some(args.next()?.parse().ok()?),
+            "--frames" => frames = Some(args.next()?),
+            "--resume" => resume = Some(args.next()?),
+            "--save-on-exit" => save_on_exit = Some(args.next()?),
+            "--profile" => profile = Some(args.next()?),
+            "--protect" => protect = true,
+            "--stack-size" => stack_size = Some(args.next()?.parse().ok()?),
+            _ if !arg.starts_with("--") && filename.is_none() => filename = Some(arg),
+            _ => return None,
+        }
+    }
+    let (filename, resume) = match (filename, resume) {
+        (Some(filename), None) => (filename, false),
+        // A snapshot brings its own protected regions
+        (None, Some(filename)) if !protect && stack_size.is_none() => (filename, true),
+        _ => return None,
+    };
+    Some(Options {
+        filename,
+        resume,
+        trace,
+        symbols,
+        max_steps,
+        memory_size,
+        frames,
+        save_on_exit,
+        profile,
+        protect,
+        stack_size,
+    })
+}
+
+// Exit status for each kind of failure
+const EXIT_USAGE: u8 = 2;
+const EXIT_UNREADABLE: u8 = 3;
+const EXIT_TOO_LARGE: u8 = 4;
+const EXIT_UNWRITABLE: u8 = 5;
+const EXIT_SNAPSHOT: u8 = 6;
+const EXIT_MEMORY: u8 = 10;
+const EXIT_REGISTER: u8 = 11;
+const EXIT_INSTRUCTION: u8 = 12;
+const EXIT_IO: u8 = 13;
+const EXIT_STEP_LIMIT: u8 = 14;
+const EXIT_INPUT: u8 = 15;
+const EXIT_DIVISION: u8 = 16;
+const EXIT_STACK: u8 = 17;
+const EXIT_PROTECTION: u8 = 18;
+
+fn exit_code(error: &MachineError) -> u8 {
+    match error {
+        MachineError::InvalidMemoryAccess { .. } => EXIT_MEMORY,
+        MachineError::InvalidRegisterAccess { .. } => EXIT_REGISTER,
+        MachineError::InvalidInstruction { .. } => EXIT_INSTRUCTION,
+        MachineError::IOError(_) => EXIT_IO,
+        MachineError::StepLimitExceeded { .. } => EXIT_STEP_LIMIT,
+        MachineError::ImageTooLarge { .. } | MachineError::MemoryTooLarge { .. } => EXIT_TOO_LARGE,
+        MachineError::InvalidNumber { .. } => EXIT_INPUT,
+        MachineError::DivisionByZero { .. } => EXIT_DIVISION,
+        MachineError::StackOverflow { .. } | MachineError::StackUnderflow { .. } => EXIT_STACK,
+        MachineError::ReadProtected { .. }
+        | MachineError::WriteProtected { .. }
+        | MachineError::ExecuteProtected { .. } => EXIT_PROTECTION,
+        MachineError::InvalidSnapshot { .. } => EXIT_SNAPSHOT,
+    }
+}
+
+fn main() -> ExitCode {
+    // Take a filename and options on the command line
+    let Some(options) = parse_args() else {
+        eprintln!("{}", USAGE);
+        return ExitCode::from(EXIT_USAGE);
+    };
 
     // Read content to buffer
-    let mut fs = File::open(&filename).unwrap();
-    let mut buffer = Vec::new();
-    fs.read_to_end(&mut buffer).unwrap();
+    let buffer = match std::fs::read(&options.filename) {
+        Ok(buffer) => buffer,
+        Err(e) => {
+            eprintln!("{}: {}", options.filename, e);
+            return ExitCode::from(EXIT_UNREADABLE);
+        }
+    };
+
+    // A snapshot brings its own geometry, and memory content once restored
+    let snapshot = if options.resume {
+        match Snapshot::from_bytes(&buffer) {
+            Ok(snapshot) => Some(snapshot),
+            Err(error) => {
+                eprintln!("{}: {}", options.filename, error);
+                return ExitCode::from(exit_code(&error));
+            }
+        }
+    } else {
+        None
+    };
 
     // Create a machine with this memory content
-    let mut machine = Machine::new(&buffer);
+    let mut config = MachineConfig::default();
+    if let Some(memory_size) = options.memory_size {
+        config.memory_size = memory_size;
+    }
+    let image = match &snapshot {
+        Some(snapshot) => {
+            config = snapshot.config();
+            &[][..]
+        }
+        None => &buffer[..],
+    };
+    let mut machine = match Machine::with_config(image, config) {
+        Ok(machine) => machine,
+        Err(error) => {
+            eprintln!("{}: {}", options.filename, error);
+            return ExitCode::from(exit_code(&error));
+        }
+    };
+
+    // Protect the program image and the stack against each other
+    if options.protect {
+        machine.protect_image();
+    }
+    if let Some(stack_size) = options.stack_size {
+        if stack_size as usize > config.memory_size - image.len() {
+            eprintln!(
+                "{}: a stack of {} bytes does not fit after the program",
+                options.filename, stack_size
+            );
+            return ExitCode::from(EXIT_TOO_LARGE);
+        }
+        machine.protect_stack(stack_size);
+    }
+
+    // Devices live at the top of the address space, above any usable memory,
+    // and get their interrupt line from the order they are attached in
+    if config.memory_size <= CONSOLE_BASE as usize {
+        machine.attach(
+            CONSOLE_BASE,
+            Box::new(Console::new(io::stdout()).with_input(io::stdin())),
+        );
+        machine.attach(TIMER_BASE, Box::new(Timer::new()));
+        // Images go to the frames file in the SE203 format, or nowhere
+        let framebuffer = match &options.frames {
+            Some(filename) => match std::fs::File::create(filename) {
+                Ok(file) => Framebuffer::new(file),
+                Err(e) => {
+                    eprintln!("{}: {}", filename, e);
+                    return ExitCode::from(EXIT_UNWRITABLE);
+                }
+            },
+            None => Framebuffer::new(io::sink()),
+        };
+        machine.attach(FRAMEBUFFER_BASE, Box::new(framebuffer));
+    }
+    if let Some(snapshot) = &snapshot {
+        if let Err(error) = machine.restore(snapshot) {
+            eprintln!("{}: {}", options.filename, error);
+            return ExitCode::from(exit_code(&error));
+        }
+    }
+
+    // Read the listing giving labels to addresses
+    let mut listing = None;
+    if let Some(filename) = &options.symbols {
+        let symbols = std::fs::read_to_string(filename)
+            .map_err(|e| e.to_string())
+            .and_then(|text| {
+                let symbols = Symbols::from_listing(&text).map_err(|e| e.to_string())?;
+                Ok((text, symbols))
+            });
+        match symbols {
+            Ok(symbols) => listing = Some(symbols),
+            Err(e) => {
+                eprintln!("{}: {}", filename, e);
+                return ExitCode::from(EXIT_UNREADABLE);
+            }
+        }
+    }
+
+    // Trace on standard error, so that the program output stays apart
+    if options.trace {
+        let mut tracer = Tracer::new(io::stderr());
+        if let Some((_, symbols)) = &listing {
+            tracer = tracer.with_symbols(symbols.clone());
+        }
+        machine.set_tracer(Some(tracer));
+    }
+
+    if options.profile.is_some() {
+        machine.set_profile(Some(Profile::new()));
+    }
+    machine.set_decode_cache(true);
+
+    // Run the machine on the standard streams until the end, or until it
+    // exhausts its steps. The input is not locked, as the console shares it.
+    let mut input = io::stdin();
+    let mut output = io::stdout().lock();
+    let result = match options.max_steps {
+        Some(max_steps) => machine.run_io_with_limit(&mut input, &mut output, max_steps),
+        None => machine.run_io(&mut input, &mut output),
+    };
+
+    // Save the machine as it stopped, whether it terminated or not
+    if let Some(filename) = &options.save_on_exit {
+        let bytes = match machine.snapshot().to_bytes() {
+            Ok(bytes) => bytes,
+            Err(error) => {
+                eprintln!("{}: {}", filename, error);
+                return ExitCode::from(exit_code(&error));
+            }
+        };
+        if let Err(e) = std::fs::write(filename, bytes) {
+            eprintln!("{}: {}", filename, e);
+            return ExitCode::from(EXIT_UNWRITABLE);
+        }
+    }
 
-    // Run the machine until the end
-    machine.run()
+    // Annotate the listing of the program, or the disassembly of its image
+    // when no listing was given and it was not resumed from a snapshot
+    if let (Some(filename), Some(profile)) = (&options.profile, machine.profile()) {
+        let (text, symbols) = listing.clone().unwrap_or_else(|| {
+            let text = if options.resume {
+                String::new()
+            } else {
+                disassemble(&buffer)
+            };
+            let symbols = Symbols::from_listing(&text).unwrap_or_default();
+            (text, symbols)
+        });
+        let report = format!(
+            "{}\n{}",
+            profile.summary(&symbols, 10),
+            profile.annotate(&text)
+        );
+        if let Err(e) = std::fs::write(filename, report) {
+            eprintln!("{}: {}", filename, e);
+            return ExitCode::from(EXIT_UNWRITABLE);
+        }
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}: {}", options.filename, error);
+            // Show the calls which led to a fault of the program
+            if let Some(ip) = error.ip() {
+                let backtrace = Backtrace {
+                    ip,
+                    frames: &machine.backtrace(),
+                    symbols: listing.as_ref().map(|(_, symbols)| symbols),
+                };
+                eprint!("backtrace:\n{}", backtrace);
+            }
+            ExitCode::from(exit_code(&error))
+        }
+    }
 }
@@ -0,0 +1,153 @@
+use crate::symbols::instruction_address;
+use crate::{Instruction, Symbols};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Execution statistics gathered while a profile is set with
+/// [Machine::set_profile](crate::Machine::set_profile): how many times
+/// each address and each opcode was executed, and how many 32-bit words
+/// were read from and written to memory and devices.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    hits: BTreeMap<u32, u64>,
+    opcodes: BTreeMap<u8, u64>,
+    reads: u64,
+    writes: u64,
+}
+
+// Name of an opcode, which can be invalid.
+fn opcode_name(opcode: u8) -> String {
+    match Instruction::decode(&[opcode, 0, 0, 0]) {
+        Ok((instruction, _)) => instruction.mnemonic().to_string(),
+        Err(_) => format!("opcode {}", opcode),
+    }
+}
+
+// Share of `count` in `total`, in percents.
+fn percent(count: u64, total: u64) -> f64 {
+    100.0 * count as f64 / total.max(1) as f64
+}
+
+impl Profile {
+    /// Create an empty profile.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record an instruction executed at `ip`, whose opcode could be read
+    /// unless it lies out of memory, with the number of words it read and
+    /// wrote.
+    pub(crate) fn record(&mut self, ip: u32, opcode: Option<u8>, reads: u64, writes: u64) {
+        *self.hits.entry(ip).or_default() += 1;
+        if let Some(opcode) = opcode {
+            *self.opcodes.entry(opcode).or_default() += 1;
+        }
+        self.reads += reads;
+        self.writes += writes;
+    }
+
+    /// Number of instructions executed.
+    pub fn steps(&self) -> u64 {
+        self.hits.values().sum()
+    }
+
+    /// Number of times the instruction at `address` was executed.
+    pub fn hits(&self, address: u32) -> u64 {
+        self.hits.get(&address).copied().unwrap_or(0)
+    }
+
+    /// Executed addresses with their number of executions, in increasing
+    /// address order.
+    pub fn addresses(&self) -> impl Iterator<Item = (u32, u64)> + '_ {
+        self.hits.iter().map(|(&address, &hits)| (address, hits))
+    }
+
+    /// Number of times an instruction with this opcode was executed.
+    pub fn opcode_hits(&self, opcode: u8) -> u64 {
+        self.opcodes.get(&opcode).copied().unwrap_or(0)
+    }
+
+    /// Number of words read by `load`, `pop` and `ret` instructions.
+    pub fn reads(&self) -> u64 {
+        self.reads
+    }
+
+    /// Number of words written by `store`, `push` and `call` instructions.
+    pub fn writes(&self) -> u64 {
+        self.writes
+    }
+
+    /// Executions grouped by the closest label at or before each address,
+    /// most executed first. Executions before the first label are grouped
+    /// under `????`.
+    pub fn hottest(&self, symbols: &Symbols) -> Vec<(String, u64)> {
+        let mut labels: BTreeMap<&str, u64> = BTreeMap::new();
+        for (&address, &hits) in &self.hits {
+            let label = symbols.lookup(address).map_or("????", |(label, _)| label);
+            *labels.entry(label).or_default() += hits;
+        }
+        let mut hottest: Vec<(String, u64)> = labels
+            .into_iter()
+            .map(|(label, hits)| (label.to_string(), hits))
+            .collect();
+        hottest.sort_by_key(|&(_, hits)| Reverse(hits));
+        hottest
+    }
+
+    /// Copy of a `.dis` listing with the number of executions of each
+    /// instruction in the left margin, or `-` for instructions never
+    /// executed:
+    ///
+    /// ```text
+    ///             loop:
+    ///        100    0008   loadimm r3 <- #4
+    ///        100    0012   sub r2 <- r2 - r3
+    ///          -    0016   store [r2] <- r10
+    /// ```
+    pub fn annotate(&self, listing: &str) -> String {
+        let mut out = String::new();
+        for line in listing.lines() {
+            match instruction_address(line).map(|address| self.hits(address)) {
+                Some(0) => writeln!(out, "{:>10}  {}", "-", line),
+                Some(hits) => writeln!(out, "{:>10}  {}", hits, line),
+                None => writeln!(out, "{:>10}  {}", "", line),
+            }
+            .unwrap();
+        }
+        out
+    }
+
+    /// Totals, executions per opcode and the `count` hottest labels of
+    /// `symbols`, most executed first.
+    pub fn summary(&self, symbols: &Symbols, count: usize) -> String {
+        let steps = self.steps();
+        let mut out = String::new();
+        writeln!(out, "{} instructions executed", steps).unwrap();
+        writeln!(
+            out,
+            "{} words read and {} words written",
+            self.reads, self.writes
+        )
+        .unwrap();
+        writeln!(out, "\nopcodes:").unwrap();
+        let mut opcodes: Vec<_> = self.opcodes.iter().collect();
+        opcodes.sort_by_key(|&(_, &hits)| Reverse(hits));
+        for (&opcode, &hits) in opcodes {
+            let share = percent(hits, steps);
+            writeln!(out, "{:>10} {:>5.1}%  {}", hits, share, opcode_name(opcode)).unwrap();
+        }
+        writeln!(out, "\nhottest labels:").unwrap();
+        for (label, hits) in self.hottest(symbols).into_iter().take(count) {
+            writeln!(
+                out,
+                "{:>10} {:>5.1}%  {}",
+                hits,
+                percent(hits, steps),
+                label
+            )
+            .unwrap();
+        }
+        out
+    }
+}
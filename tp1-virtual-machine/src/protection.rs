@@ -0,0 +1,96 @@
+use std::fmt;
+use std::ops::Range;
+
+/// Accesses allowed within a [Region].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Permissions {
+    /// Words can be read by `load`, `pop` and `ret`.
+    pub read: bool,
+    /// Words can be written by `store`, `push` and `call`.
+    pub write: bool,
+    /// Instructions can be executed.
+    pub execute: bool,
+}
+
+impl Permissions {
+    /// Read-only data.
+    pub const R: Self = Permissions {
+        read: true,
+        write: false,
+        execute: false,
+    };
+    /// Code, which can also be read as data.
+    pub const RX: Self = Permissions {
+        read: true,
+        write: false,
+        execute: true,
+    };
+    /// Data, such as the stack.
+    pub const RW: Self = Permissions {
+        read: true,
+        write: true,
+        execute: false,
+    };
+    /// Everything.
+    pub const RWX: Self = Permissions {
+        read: true,
+        write: true,
+        execute: true,
+    };
+}
+
+impl fmt::Display for Permissions {
+    /// Show the permissions as `ls` does, such as `r-x`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flag = |allowed, c| if allowed { c } else { '-' };
+        write!(
+            f,
+            "{}{}{}",
+            flag(self.read, 'r'),
+            flag(self.write, 'w'),
+            flag(self.execute, 'x')
+        )
+    }
+}
+
+/// Named range of addresses, restricting the accesses made within it, set
+/// with [Machine::protect](crate::Machine::protect).
+///
+/// An access touching several regions must be allowed by all of them, and
+/// addresses outside every region can be accessed freely.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// Name used in errors, such as `text` or `stack`.
+    pub name: String,
+    /// Addresses covered by the region.
+    pub range: Range<u32>,
+    /// Accesses allowed within the region.
+    pub permissions: Permissions,
+}
+
+impl Region {
+    /// Create a region called `name` covering `range`.
+    pub fn new(name: impl Into<String>, range: Range<u32>, permissions: Permissions) -> Self {
+        Region {
+            name: name.into(),
+            range,
+            permissions,
+        }
+    }
+
+    /// Whether the region contains one of the `width` bytes at `address`.
+    pub(crate) fn overlaps(&self, address: u32, width: u32) -> bool {
+        let end = address as u64 + width as u64;
+        (address as u64) < self.range.end as u64 && end > self.range.start as u64
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:04} {} {}",
+            self.range.start, self.range.end, self.permissions, self.name
+        )
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{MachineConfig, MachineError, Permissions, Region, CONTROL_REGISTERS};
+
+/// Bytes starting every saved snapshot.
+const MAGIC: &[u8; 8] = b"SE202VM\0";
+/// Version of the snapshot format written by [Snapshot::to_bytes].
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// Complete state of a [Machine](crate::Machine), taken with
+/// [Machine::snapshot](crate::Machine::snapshot) and given back with
+/// [Machine::restore](crate::Machine::restore). The tracer is not part of
+/// it.
+///
+/// Its saved form is made of little-endian 32-bit numbers and byte strings
+/// prefixed by their length:
+///
+/// - the magic bytes `SE202VM\0` and the format version, currently 2;
+/// - the registers and the control registers, each preceded by their count;
+/// - a flags word, whose bit 0 is set within a trap handler and bit 1
+///   within an interrupt handler;
+/// - the stack limit and the size of the program image;
+/// - the number of protected regions, then the name, start, end and
+///   permissions of each region, whose bits 0, 1 and 2 allow reading,
+///   writing and executing;
+/// - the memory;
+/// - the number of devices, then the state of each device in the order
+///   they were attached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub(crate) reg: Vec<u32>,
+    pub(crate) ctl: [u32; CONTROL_REGISTERS],
+    pub(crate) trapped: bool,
+    pub(crate) interrupted: bool,
+    pub(crate) stack_limit: u32,
+    pub(crate) image_size: u32,
+    pub(crate) regions: Vec<Region>,
+    pub(crate) memory: Vec<u8>,
+    pub(crate) devices: Vec<Vec<u8>>,
+}
+
+fn invalid(reason: &str) -> MachineError {
+    MachineError::InvalidSnapshot {
+        reason: reason.to_owned(),
+    }
+}
+
+// Reader of the fields of a saved snapshot.
+struct Fields<'a>(&'a [u8]);
+
+impl<'a> Fields<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], MachineError> {
+        if self.0.len() < len {
+            return Err(invalid("truncated data"));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn word(&mut self) -> Result<u32, MachineError> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn words(&mut self) -> Result<Vec<u32>, MachineError> {
+        let len = self.word()? as usize;
+        (0..len).map(|_| self.word()).collect()
+    }
+
+    fn string(&mut self) -> Result<&'a [u8], MachineError> {
+        let len = self.word()? as usize;
+        self.bytes(len)
+    }
+
+    fn region(&mut self) -> Result<Region, MachineError> {
+        let name = std::str::from_utf8(self.string()?)
+            .map_err(|_| invalid("invalid region name"))?
+            .to_owned();
+        let range = self.word()?..self.word()?;
+        let permissions = self.word()?;
+        let permissions = Permissions {
+            read: permissions & 1 != 0,
+            write: permissions & 2 != 0,
+            execute: permissions & 4 != 0,
+        };
+        Ok(Region::new(name, range, permissions))
+    }
+}
+
+fn put_word(out: &mut Vec<u8>, word: u32) {
+    out.extend(word.to_le_bytes());
+}
+
+// Write the length of a list or of a string, which must fit in a word.
+fn put_len(out: &mut Vec<u8>, len: usize) -> Result<(), MachineError> {
+    let len = u32::try_from(len).map_err(|_| invalid("too large to be saved"))?;
+    put_word(out, len);
+    Ok(())
+}
+
+fn put_string(out: &mut Vec<u8>, bytes: &[u8]) -> Result<(), MachineError> {
+    put_len(out, bytes.len())?;
+    out.extend(bytes);
+    Ok(())
+}
+
+impl Snapshot {
+    /// Geometry of the machine this snapshot was taken from.
+    pub fn config(&self) -> MachineConfig {
+        MachineConfig {
+            memory_size: self.memory.len(),
+            registers: self.reg.len(),
+        }
+    }
+
+    /// Save the snapshot in the format described above, or return
+    /// [MachineError::InvalidSnapshot] if a part of it is too large for it.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MachineError> {
+        let mut out = MAGIC.to_vec();
+        put_word(&mut out, SNAPSHOT_VERSION);
+        put_word(&mut out, self.reg.len() as u32);
+        self.reg.iter().for_each(|&r| put_word(&mut out, r));
+        put_word(&mut out, CONTROL_REGISTERS as u32);
+        self.ctl.iter().for_each(|&c| put_word(&mut out, c));
+        put_word(
+            &mut out,
+            self.trapped as u32 | (self.interrupted as u32) << 1,
+        );
+        put_word(&mut out, self.stack_limit);
+        put_word(&mut out, self.image_size);
+        put_len(&mut out, self.regions.len())?;
+        for region in &self.regions {
+            let permissions = &region.permissions;
+            put_string(&mut out, region.name.as_bytes())?;
+            put_word(&mut out, region.range.start);
+            put_word(&mut out, region.range.end);
+            put_word(
+                &mut out,
+                permissions.read as u32
+                    | (permissions.write as u32) << 1
+                    | (permissions.execute as u32) << 2,
+            );
+        }
+        put_string(&mut out, &self.memory)?;
+        put_len(&mut out, self.devices.len())?;
+        for device in &self.devices {
+            put_string(&mut out, device)?;
+        }
+        Ok(out)
+    }
+
+    /// Load a snapshot saved by [to_bytes](Snapshot::to_bytes), or return
+    /// [MachineError::InvalidSnapshot] if `bytes` is not one.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MachineError> {
+        let mut fields = Fields(bytes);
+        if fields.bytes(MAGIC.len()).ok() != Some(&MAGIC[..]) {
+            return Err(invalid("not a snapshot"));
+        }
+        let version = fields.word()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(MachineError::InvalidSnapshot {
+                reason: format!("unsupported version {}", version),
+            });
+        }
+        let reg = fields.words()?;
+        if !(1..=256).contains(&reg.len()) {
+            return Err(invalid("invalid number of registers"));
+        }
+        let ctl = fields
+            .words()?
+            .try_into()
+            .map_err(|_| invalid("invalid number of control registers"))?;
+        let flags = fields.word()?;
+        let stack_limit = fields.word()?;
+        let image_size = fields.word()?;
+        let regions = (0..fields.word()?)
+            .map(|_| fields.region())
+            .collect::<Result<_, _>>()?;
+        let memory = fields.string()?.to_vec();
+        let devices = (0..fields.word()?)
+            .map(|_| fields.string().map(<[u8]>::to_vec))
+            .collect::<Result<_, _>>()?;
+        if !fields.0.is_empty() {
+            return Err(invalid("trailing data"));
+        }
+        Ok(Snapshot {
+            reg,
+            ctl,
+            trapped: flags & 1 != 0,
+            interrupted: flags & 2 != 0,
+            stack_limit,
+            image_size,
+            regions,
+            memory,
+            devices,
+        })
+    }
+}
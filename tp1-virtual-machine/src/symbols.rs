@@ -0,0 +1,67 @@
+use crate::{assemble, AssemblerError, Program};
+
+/// Address of the instruction held by a line of a `.dis` listing, if the
+/// line has an address column and is not data.
+pub(crate) fn instruction_address(line: &str) -> Option<u32> {
+    let mut words = line.split_whitespace();
+    let column = words.next().filter(|column| column.len() == 4)?;
+    let statement = words.next()?;
+    if statement.starts_with('[') || statement.starts_with("b'") {
+        return None;
+    }
+    column.parse().ok()
+}
+
+/// Labels of a program, used to show addresses as `label+offset`.
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    // Sorted by address; for labels sharing an address, the first defined
+    // one comes first.
+    labels: Vec<(u32, String)>,
+}
+
+impl Symbols {
+    /// Symbols of an assembled program.
+    pub fn from_program(program: &Program) -> Self {
+        let mut labels: Vec<(u32, String)> = program
+            .labels
+            .iter()
+            .map(|(label, address)| (*address, label.clone()))
+            .collect();
+        labels.sort_by_key(|(address, _)| *address);
+        Symbols { labels }
+    }
+
+    /// Symbols of a `.dis` listing.
+    pub fn from_listing(listing: &str) -> Result<Self, AssemblerError> {
+        Ok(Self::from_program(&assemble(listing)?))
+    }
+
+    /// Address of the label called `name`, if it is defined.
+    pub fn address(&self, name: &str) -> Option<u32> {
+        self.labels
+            .iter()
+            .find(|(_, label)| label == name)
+            .map(|(address, _)| *address)
+    }
+
+    /// Closest label at or before `address`, with the offset from it.
+    pub fn lookup(&self, address: u32) -> Option<(&str, u32)> {
+        let index = self.labels.partition_point(|(a, _)| *a <= address);
+        let (start, _) = self.labels.get(index.checked_sub(1)?)?;
+        // Take the first label defined at that address
+        let first = self.labels.partition_point(|(a, _)| a < start);
+        let (start, label) = &self.labels[first];
+        Some((label, address - start))
+    }
+
+    /// Show `address` as `label` or `label+offset`, or as a plain number
+    /// when no label precedes it.
+    pub fn name(&self, address: u32) -> String {
+        match self.lookup(address) {
+            Some((label, 0)) => label.to_string(),
+            Some((label, offset)) => format!("{}+{}", label, offset),
+            None => format!("{:04}", address),
+        }
+    }
+}
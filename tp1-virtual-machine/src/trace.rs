@@ -0,0 +1,81 @@
+use crate::{Instruction, MachineError, Symbols};
+use std::io::{self, Write};
+
+/// Destination of the execution trace set with
+/// [Machine::set_tracer](crate::Machine::set_tracer).
+///
+/// One line is written per executed instruction, with its address, the
+/// instruction and the registers and memory it changed:
+///
+/// ```text
+///   0111 ite_then_2            loadimm r3 <- #4             r3=4
+///   0119 ite_then_2+8          store [r2] <- r10            [4084]=5
+///   0145 ite_then_2+34         loadimm r0 <- #87            -> rfact
+/// ```
+pub struct Tracer {
+    out: Box<dyn Write>,
+    symbols: Option<Symbols>,
+}
+
+impl Tracer {
+    /// Create a tracer writing on `out`.
+    pub fn new(out: impl Write + 'static) -> Self {
+        Tracer {
+            out: Box::new(out),
+            symbols: None,
+        }
+    }
+
+    /// Annotate addresses with the labels of `symbols`.
+    pub fn with_symbols(mut self, symbols: Symbols) -> Self {
+        self.symbols = Some(symbols);
+        self
+    }
+
+    fn name(&self, address: u32) -> String {
+        match &self.symbols {
+            Some(symbols) => symbols.name(address),
+            None => format!("{:04}", address),
+        }
+    }
+
+    /// Write the line of an instruction executed at `ip`. `before` and
+    /// `after` are the registers around the execution, and `stored` the
+    /// address and value of the memory word written, if any.
+    pub(crate) fn record(
+        &mut self,
+        ip: u32,
+        instruction: Option<Instruction>,
+        before: &[u32],
+        after: &[u32],
+        stored: Option<(u32, u32)>,
+        error: Option<&MachineError>,
+    ) -> io::Result<()> {
+        let location = match &self.symbols {
+            Some(symbols) if symbols.lookup(ip).is_some() => {
+                format!("{:04} {:<21}", ip, symbols.name(ip))
+            }
+            Some(_) => format!("{:04} {:<21}", ip, ""),
+            None => format!("{:04}", ip),
+        };
+        let text = instruction.map_or("????".to_string(), |i| i.to_string());
+        let mut changes = Vec::new();
+        let next = ip.wrapping_add(instruction.map_or(0, |i| i.size() as u32));
+        if error.is_none() && after[0] != next {
+            changes.push(format!("-> {}", self.name(after[0])));
+        }
+        for (reg, (old, new)) in before.iter().zip(after).enumerate().skip(1) {
+            if old != new {
+                changes.push(format!("r{}={}", reg, *new as i32));
+            }
+        }
+        if let Some((address, value)) = stored {
+            changes.push(format!("[{}]={}", address, value as i32));
+        }
+        if let Some(error) = error {
+            changes.push(format!("!! {}", error));
+        }
+        let line = format!("  {} {:<28} {}", location, text, changes.join(" "));
+        writeln!(self.out, "{}", line.trim_end())
+    }
+}
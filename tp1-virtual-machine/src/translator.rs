@@ -0,0 +1,546 @@
+use crate::{Instruction, Machine, MachineConfig, MachineError};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+
+const NREGS: usize = 16;
+const IP: u8 = 0;
+const SP: usize = 2;
+
+/// Memory of a [Translated] program. Its methods are called by the
+/// translated code, and make the checks the interpreter would make: when
+/// they fail, or when a store would modify the code, nothing is changed and
+/// the translated code leaves the instruction to the interpreter.
+pub struct Memory {
+    bytes: Vec<u8>,
+    image_size: u32,
+}
+
+impl Memory {
+    // Range of the word at `address`, if it lies in memory.
+    fn word(&self, address: u32) -> Option<std::ops::Range<usize>> {
+        let start = address as usize;
+        (start + 4 <= self.bytes.len()).then_some(start..start + 4)
+    }
+
+    /// Word loaded by `load` from `address`.
+    #[inline]
+    pub fn load(&self, address: u32) -> Option<u32> {
+        let range = self.word(address)?;
+        Some(u32::from_le_bytes(self.bytes[range].try_into().unwrap()))
+    }
+
+    /// Store `value` at `address`, unless this would modify the program
+    /// image. `false` is returned if nothing was stored.
+    #[inline]
+    pub fn store(&mut self, address: u32, value: u32) -> bool {
+        match self.word(address) {
+            Some(range) if address >= self.image_size => {
+                self.bytes[range].copy_from_slice(&value.to_le_bytes());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Push `value` on the stack at `sp`, returning the new stack pointer.
+    #[inline]
+    pub fn push(&mut self, sp: u32, value: u32) -> Option<u32> {
+        if sp < self.image_size.saturating_add(4) {
+            return None;
+        }
+        self.store(sp - 4, value).then_some(sp - 4)
+    }
+
+    /// Pop a value from the stack at `sp`, returning the new stack pointer
+    /// and the value.
+    #[inline]
+    pub fn pop(&self, sp: u32) -> Option<(u32, u32)> {
+        let value = self.load(sp)?;
+        Some((sp + 4, value))
+    }
+}
+
+/// Translated code of a program: it runs from the IP in `r`, and returns
+/// `true` when the program exits, or `false` when the instruction at the IP
+/// must be left to the interpreter.
+pub type Code = fn(&mut [u32; NREGS], &mut Memory, &mut dyn Write) -> Result<bool, MachineError>;
+
+/// Program translated to Rust by [translate], with the same view of its
+/// registers and memory as a [Machine] created from its image.
+///
+/// The translated code runs until it meets an instruction it leaves to the
+/// interpreter, such as a store modifying the program, an instruction
+/// reading input or control registers, or a fault. The state is then
+/// moved into a machine, which runs the rest of the program.
+pub struct Translated {
+    reg: [u32; NREGS],
+    memory: Memory,
+    code: Code,
+    machine: Option<Machine>,
+}
+
+impl Translated {
+    /// Create a program translated from `image` into `code`, in the reset
+    /// state of a machine.
+    ///
+    /// # Panics
+    /// This function panics when `image` is larger than the machine memory.
+    pub fn new(image: &[u8], code: Code) -> Self {
+        let mut bytes = vec![0; MachineConfig::default().memory_size];
+        assert!(
+            image.len() <= bytes.len(),
+            "The image must not be bigger than {} bytes",
+            bytes.len()
+        );
+        bytes[..image.len()].copy_from_slice(image);
+        Translated {
+            reg: [0; NREGS],
+            memory: Memory {
+                bytes,
+                image_size: image.len() as u32,
+            },
+            code,
+            machine: None,
+        }
+    }
+
+    /// Whether the rest of the program is left to the interpreter.
+    pub fn interpreted(&self) -> bool {
+        self.machine.is_some()
+    }
+
+    /// Reference onto the current set of registers.
+    pub fn regs(&self) -> &[u32] {
+        match &self.machine {
+            Some(machine) => machine.regs(),
+            None => &self.reg,
+        }
+    }
+
+    /// Sets a register to the given value.
+    pub fn set_reg(&mut self, reg: usize, value: u32) -> Result<(), MachineError> {
+        match &mut self.machine {
+            Some(machine) => machine.set_reg(reg, value),
+            None if reg < NREGS => {
+                self.reg[reg] = value;
+                Ok(())
+            }
+            None => Err(MachineError::InvalidRegisterAccess {
+                ip: self.reg[IP as usize],
+                opcode: None,
+                register: reg,
+                host: true,
+            }),
+        }
+    }
+
+    /// Reference onto the current memory.
+    pub fn memory(&self) -> &[u8] {
+        match &self.machine {
+            Some(machine) => machine.memory(),
+            None => &self.memory.bytes,
+        }
+    }
+
+    /// Run until the program terminates or until an error happens, as
+    /// [Machine::run_io] does.
+    pub fn run_io<R: Read, W: Write>(
+        &mut self,
+        input: &mut R,
+        output: &mut W,
+    ) -> Result<(), MachineError> {
+        if self.machine.is_none() {
+            if (self.code)(&mut self.reg, &mut self.memory, output)? {
+                return Ok(());
+            }
+            let memory = &self.memory.bytes;
+            let mut machine = Machine::new(&memory[..self.memory.image_size as usize]);
+            machine.set_memory(0, memory)?;
+            for (reg, &value) in self.reg.iter().enumerate() {
+                machine.set_reg(reg, value)?;
+            }
+            self.machine = Some(machine);
+        }
+        self.machine.as_mut().unwrap().run_io(input, output)
+    }
+
+    /// Similar to [run_io](Translated::run_io), with an empty input.
+    pub fn run_on<T: Write>(&mut self, fd: &mut T) -> Result<(), MachineError> {
+        self.run_io(&mut std::io::empty(), fd)
+    }
+}
+
+// How a block of instructions ends.
+enum End {
+    // With its last instruction, which writes the IP or exits
+    Jump,
+    // By going on with the block starting at this address
+    Next(u32),
+    // By leaving the instruction at this address to the interpreter
+    Interpret(u32),
+}
+
+// Instructions of the block starting at `start`, which stops before the
+// next block in `starts`, and the addresses it may continue at.
+fn block(
+    image: &[u8],
+    start: u32,
+    starts: &BTreeSet<u32>,
+) -> (Vec<(u32, Instruction)>, End, Vec<u32>) {
+    let mut instructions = Vec::new();
+    let mut targets = Vec::new();
+    // Registers holding a known value, to follow `move r0 <- rX if rY`
+    let mut known: HashMap<u8, u32> = HashMap::new();
+    let mut address = start;
+    loop {
+        let decoded = image
+            .get(address as usize..)
+            .and_then(|bytes| Instruction::decode(bytes).ok())
+            .filter(|(i, _)| translated(i));
+        let Some((instruction, size)) = decoded else {
+            return (instructions, End::Interpret(address), targets);
+        };
+        let next = address + size as u32;
+        instructions.push((address, instruction));
+        match instruction {
+            Instruction::LoadImm(IP, value) => targets.push(value as u32),
+            Instruction::MoveIf(IP, b, _) => {
+                targets.extend(known.get(&b).copied().into_iter().chain([next]))
+            }
+            Instruction::Jump(offset) => targets.push(next.wrapping_add(offset as u32)),
+            Instruction::BranchZero(_, offset) | Instruction::BranchNonZero(_, offset) => {
+                targets.extend([next.wrapping_add(offset as u32), next])
+            }
+            Instruction::Call(target) => targets.extend([target as u32, next]),
+            _ => (),
+        }
+        if jumps(&instruction) {
+            return (instructions, End::Jump, targets);
+        }
+        match instruction {
+            Instruction::LoadImm(a, value) => {
+                known.insert(a, value as u32);
+            }
+            _ => {
+                if let Some(a) = instruction.destination() {
+                    known.remove(&a);
+                }
+            }
+        }
+        if starts.contains(&next) {
+            targets.push(next);
+            return (instructions, End::Next(next), targets);
+        }
+        address = next;
+    }
+}
+
+// Whether the instruction is translated rather than left to the
+// interpreter.
+fn translated(instruction: &Instruction) -> bool {
+    !matches!(
+        instruction,
+        Instruction::In(_)
+            | Instruction::InNumber(_)
+            | Instruction::ReadControl(..)
+            | Instruction::WriteControl(..)
+            | Instruction::ReturnFromTrap
+            | Instruction::ReturnFromInterrupt
+    ) && instruction
+        .max_register()
+        .is_none_or(|r| (r as usize) < NREGS)
+}
+
+// Whether the instruction ends a block by writing the IP or exiting.
+fn jumps(instruction: &Instruction) -> bool {
+    instruction.destination() == Some(IP)
+        || matches!(
+            instruction,
+            Instruction::Exit
+                | Instruction::Call(_)
+                | Instruction::Ret
+                | Instruction::Jump(_)
+                | Instruction::BranchZero(..)
+                | Instruction::BranchNonZero(..)
+        )
+}
+
+// Addresses of the blocks of `image`: the ones reached from address 0,
+// and the ones following a jump at an address loaded by a `loadimm`, such
+// as the return addresses of the example programs.
+fn starts(image: &[u8]) -> BTreeSet<u32> {
+    let mut starts = BTreeSet::from([0]);
+    loop {
+        let mut reached = BTreeSet::new();
+        let mut after_jumps = BTreeSet::new();
+        let mut immediates = BTreeSet::new();
+        for &start in &starts {
+            let (instructions, end, targets) = block(image, start, &starts);
+            reached.extend(targets);
+            if let (Some(&(address, last)), End::Jump) = (instructions.last(), end) {
+                after_jumps.insert(address + last.size() as u32);
+            }
+            for (_, instruction) in instructions {
+                if let Instruction::LoadImm(_, value) = instruction {
+                    immediates.insert(value as u32);
+                }
+            }
+        }
+        reached.extend(after_jumps.intersection(&immediates));
+        reached.retain(|&address| (address as usize) < image.len());
+        if reached.is_subset(&starts) {
+            return starts;
+        }
+        starts.extend(reached);
+    }
+}
+
+// Value of register `reg` for the instruction ending at `next`.
+fn read(reg: u8, next: u32) -> String {
+    if reg == IP {
+        next.to_string()
+    } else {
+        format!("r[{}]", reg)
+    }
+}
+
+// Expression computed by an arithmetic instruction.
+fn arith(instruction: &Instruction, next: u32) -> Option<String> {
+    let (b, c) = match *instruction {
+        Instruction::Sub(_, b, c)
+        | Instruction::Add(_, b, c)
+        | Instruction::Mul(_, b, c)
+        | Instruction::DivS(_, b, c)
+        | Instruction::DivU(_, b, c)
+        | Instruction::RemS(_, b, c)
+        | Instruction::RemU(_, b, c)
+        | Instruction::And(_, b, c)
+        | Instruction::Or(_, b, c)
+        | Instruction::Xor(_, b, c)
+        | Instruction::Shl(_, b, c)
+        | Instruction::Shr(_, b, c)
+        | Instruction::Sar(_, b, c)
+        | Instruction::Slt(_, b, c)
+        | Instruction::SltU(_, b, c) => (read(b, next), read(c, next)),
+        Instruction::Not(_, b) => return Some(format!("!{}", read(b, next))),
+        _ => return None,
+    };
+    Some(match instruction {
+        Instruction::Sub(..) => format!("{}.wrapping_sub({})", b, c),
+        Instruction::Add(..) => format!("{}.wrapping_add({})", b, c),
+        Instruction::Mul(..) => format!("{}.wrapping_mul({})", b, c),
+        Instruction::DivS(..) => format!("({} as i32).wrapping_div({} as i32) as u32", b, c),
+        Instruction::DivU(..) => format!("{} / {}", b, c),
+        Instruction::RemS(..) => format!("({} as i32).wrapping_rem({} as i32) as u32", b, c),
+        Instruction::RemU(..) => format!("{} % {}", b, c),
+        Instruction::And(..) => format!("{} & {}", b, c),
+        Instruction::Or(..) => format!("{} | {}", b, c),
+        Instruction::Xor(..) => format!("{} ^ {}", b, c),
+        Instruction::Shl(..) => format!("{}.wrapping_shl({})", b, c),
+        Instruction::Shr(..) => format!("{}.wrapping_shr({})", b, c),
+        Instruction::Sar(..) => format!("({} as i32).wrapping_shr({}) as u32", b, c),
+        Instruction::Slt(..) => format!("(({} as i32) < ({} as i32)) as u32", b, c),
+        _ => format!("({} < {}) as u32", b, c),
+    })
+}
+
+// Lines of `head` followed by a block leaving the instruction at `address`
+// to the interpreter, laid out as rustfmt does.
+fn interpret(head: String, address: u32, tail: &str) -> Vec<String> {
+    vec![
+        format!("{} {{", head),
+        format!("    r[0] = {};", address),
+        "    return Ok(false);".to_owned(),
+        format!("}}{}", tail),
+    ]
+}
+
+// Lines of an `if` expression choosing between two values.
+fn choose(test: String, then: String, otherwise: String) -> Vec<String> {
+    vec![
+        format!("if {} {{", test),
+        format!("    {}", then),
+        "} else {".to_owned(),
+        format!("    {}", otherwise),
+        "}".to_owned(),
+    ]
+}
+
+// Code of the instruction at `address`, ending at `next`, in the arm of a
+// block. For an instruction writing the IP, the last line is the value of
+// the IP, or leaves the function.
+fn instruction_code(address: u32, instruction: Instruction, next: u32) -> Vec<String> {
+    // Set register `a` to `value`, the IP being the value of the arm
+    let set = |a: u8, value: String| {
+        if a == IP {
+            value
+        } else {
+            format!("r[{}] = {};", a, value)
+        }
+    };
+    let mut lines = Vec::new();
+    match instruction {
+        Instruction::MoveIf(IP, b, c) => lines.extend(choose(
+            format!("{} != 0", read(c, next)),
+            read(b, next),
+            next.to_string(),
+        )),
+        Instruction::MoveIf(a, b, c) => lines.extend([
+            format!("if {} != 0 {{", read(c, next)),
+            format!("    r[{}] = {};", a, read(b, next)),
+            "}".to_owned(),
+        ]),
+        Instruction::Store(a, b) => lines.extend(interpret(
+            format!("if !m.store({}, {})", read(a, next), read(b, next)),
+            address,
+            "",
+        )),
+        Instruction::Load(a, b) => {
+            lines.extend(interpret(
+                format!("let Some(value) = m.load({}) else", read(b, next)),
+                address,
+                ";",
+            ));
+            lines.push(set(a, "value".to_owned()));
+        }
+        Instruction::LoadImm(a, value) => lines.push(set(a, (value as u32).to_string())),
+        Instruction::Out(a) => lines.extend([
+            format!("r[0] = {};", next),
+            format!(
+                "write!(output, \"{{}}\", {} as u8 as char)?;",
+                read(a, next)
+            ),
+        ]),
+        Instruction::OutNumber(a) => lines.extend([
+            format!("r[0] = {};", next),
+            format!("write!(output, \"{{}}\", {} as i32)?;", read(a, next)),
+        ]),
+        Instruction::Exit => {
+            lines.extend([format!("r[0] = {};", next), "return Ok(true);".to_owned()])
+        }
+        Instruction::Push(_) | Instruction::Call(_) => {
+            let value = match instruction {
+                Instruction::Push(a) => read(a, next),
+                _ => next.to_string(),
+            };
+            lines.extend(interpret(
+                format!("let Some(sp) = m.push(r[{}], {}) else", SP, value),
+                address,
+                ";",
+            ));
+            lines.push(format!("r[{}] = sp;", SP));
+            if let Instruction::Call(target) = instruction {
+                lines.push((target as u32).to_string());
+            }
+        }
+        Instruction::Pop(_) | Instruction::Ret => {
+            lines.extend(interpret(
+                format!("let Some((sp, value)) = m.pop(r[{}]) else", SP),
+                address,
+                ";",
+            ));
+            lines.push(format!("r[{}] = sp;", SP));
+            let a = match instruction {
+                Instruction::Pop(a) => a,
+                _ => IP,
+            };
+            lines.push(set(a, "value".to_owned()));
+        }
+        Instruction::Jump(offset) => lines.push(next.wrapping_add(offset as u32).to_string()),
+        Instruction::BranchZero(a, offset) | Instruction::BranchNonZero(a, offset) => {
+            let test = match instruction {
+                Instruction::BranchZero(..) => "==",
+                _ => "!=",
+            };
+            lines.extend(choose(
+                format!("{} {} 0", read(a, next), test),
+                next.wrapping_add(offset as u32).to_string(),
+                next.to_string(),
+            ));
+        }
+        Instruction::DivS(a, _, c)
+        | Instruction::DivU(a, _, c)
+        | Instruction::RemS(a, _, c)
+        | Instruction::RemU(a, _, c) => {
+            lines.extend(interpret(format!("if {} == 0", read(c, next)), address, ""));
+            lines.push(set(a, arith(&instruction, next).unwrap()));
+        }
+        _ => {
+            let a = instruction.destination().unwrap();
+            lines.push(set(a, arith(&instruction, next).unwrap()));
+        }
+    }
+    lines
+}
+
+/// Translate `image` into the source of a Rust module, whose `new`
+/// function creates a [Translated] program ready to run from address 0.
+///
+/// Every basic block found by following the jumps of the program becomes
+/// an arm of a `match` on the IP. Jumping anywhere else, such as into data
+/// or code written at run time, is left to the interpreter.
+pub fn translate(image: &[u8]) -> String {
+    let starts = starts(image);
+    let mut out = String::new();
+    writeln!(out, "// Generated by vmaot, do not edit.").unwrap();
+    writeln!(out, "#![allow(clippy::all, unused)]\n").unwrap();
+    writeln!(
+        out,
+        "use interpreter::{{MachineError, Memory, Translated}};"
+    )
+    .unwrap();
+    writeln!(out, "use std::io::Write;\n").unwrap();
+    writeln!(out, "/// Image the module was translated from.").unwrap();
+    writeln!(out, "pub const IMAGE: &[u8] = &[").unwrap();
+    // As many bytes per line as rustfmt puts
+    let mut line = String::new();
+    for byte in image {
+        let item = format!("{},", byte);
+        if !line.is_empty() && 4 + line.len() + 1 + item.len() > 99 {
+            writeln!(out, "    {}", line).unwrap();
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&item);
+    }
+    if !line.is_empty() {
+        writeln!(out, "    {}", line).unwrap();
+    }
+    writeln!(out, "];\n").unwrap();
+    writeln!(out, "/// Program ready to run from its first instruction.").unwrap();
+    writeln!(out, "pub fn new() -> Translated {{").unwrap();
+    writeln!(out, "    Translated::new(IMAGE, run)\n}}\n").unwrap();
+    writeln!(
+        out,
+        "fn run(r: &mut [u32; 16], m: &mut Memory, output: &mut dyn Write) -> Result<bool, MachineError> {{"
+    )
+    .unwrap();
+    writeln!(out, "    loop {{").unwrap();
+    writeln!(out, "        r[0] = match r[0] {{").unwrap();
+    for &start in &starts {
+        let (instructions, end, _) = block(image, start, &starts);
+        writeln!(out, "            {} => {{", start).unwrap();
+        for (address, instruction) in instructions {
+            writeln!(out, "                // {:04}   {}", address, instruction).unwrap();
+            let next = address + instruction.size() as u32;
+            for line in instruction_code(address, instruction, next) {
+                writeln!(out, "                {}", line).unwrap();
+            }
+        }
+        match end {
+            End::Jump => (),
+            End::Next(next) => writeln!(out, "                {}", next).unwrap(),
+            End::Interpret(address) => {
+                writeln!(out, "                r[0] = {};", address).unwrap();
+                writeln!(out, "                return Ok(false);").unwrap();
+            }
+        }
+        writeln!(out, "            }}").unwrap();
+    }
+    writeln!(out, "            _ => return Ok(false),").unwrap();
+    writeln!(out, "        }};\n    }}\n}}").unwrap();
+    out
+}
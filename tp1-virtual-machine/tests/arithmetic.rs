@@ -0,0 +1,87 @@
+use interpreter::{assemble, disassemble, Instruction, Machine, MachineError};
+
+// Execute `make(1, 2, 3)` with r2 = b and r3 = c, and return r1.
+fn compute(make: fn(u8, u8, u8) -> Instruction, b: u32, c: u32) -> u32 {
+    let mut machine = Machine::new(&[]);
+    machine.set_reg(2, b).unwrap();
+    machine.set_reg(3, c).unwrap();
+    machine.execute_on(make(1, 2, 3), &mut Vec::new()).unwrap();
+    machine.regs()[1]
+}
+
+#[test]
+fn wrapping_arithmetic() {
+    assert_eq!(5, compute(Instruction::Add, 2, 3));
+    assert_eq!(1, compute(Instruction::Add, 0xffffffff, 2));
+    assert_eq!(-6i32 as u32, compute(Instruction::Mul, 2, -3i32 as u32));
+    assert_eq!(0xfffffffe, compute(Instruction::Mul, 0x7fffffff, 2));
+}
+
+#[test]
+fn divisions() {
+    assert_eq!(-3i32 as u32, compute(Instruction::DivS, -7i32 as u32, 2));
+    assert_eq!(0x7ffffffc, compute(Instruction::DivU, -7i32 as u32, 2));
+    assert_eq!(-1i32 as u32, compute(Instruction::RemS, -7i32 as u32, 2));
+    assert_eq!(1, compute(Instruction::RemU, -7i32 as u32, 2));
+    // The only signed overflow wraps around
+    assert_eq!(0x80000000, compute(Instruction::DivS, 0x80000000, u32::MAX));
+    assert_eq!(0, compute(Instruction::RemS, 0x80000000, u32::MAX));
+}
+
+#[test]
+fn division_by_zero() {
+    // 0: divu r1 <- r2, r3 with r3 == 0
+    let mut machine = Machine::new(&[14, 1, 2, 3]);
+    machine.set_reg(1, 42).unwrap();
+    let error = machine.step_on(&mut Vec::new()).unwrap_err();
+    assert!(matches!(error, MachineError::DivisionByZero { ip: 0 }));
+    assert_eq!("division by zero at 0000", error.to_string());
+    assert_eq!(42, machine.regs()[1]);
+}
+
+#[test]
+fn logic_and_shifts() {
+    assert_eq!(0b1000, compute(Instruction::And, 0b1100, 0b1010));
+    assert_eq!(0b1110, compute(Instruction::Or, 0b1100, 0b1010));
+    assert_eq!(0b0110, compute(Instruction::Xor, 0b1100, 0b1010));
+    assert_eq!(
+        0xfffffff0,
+        compute(|a, b, _| Instruction::Not(a, b), 0xf, 0)
+    );
+    assert_eq!(0x10, compute(Instruction::Shl, 1, 4));
+    // Only the 5 low bits of the shift amount are used
+    assert_eq!(2, compute(Instruction::Shl, 1, 33));
+    assert_eq!(0x0fffffff, compute(Instruction::Shr, 0xffffffff, 4));
+    assert_eq!(0xffffffff, compute(Instruction::Sar, 0xffffffff, 4));
+    assert_eq!(0x07ffffff, compute(Instruction::Sar, 0x7fffffff, 4));
+}
+
+#[test]
+fn comparisons() {
+    assert_eq!(1, compute(Instruction::Slt, -1i32 as u32, 0));
+    assert_eq!(0, compute(Instruction::SltU, -1i32 as u32, 0));
+    assert_eq!(0, compute(Instruction::Slt, 3, 3));
+    assert_eq!(1, compute(Instruction::SltU, 2, 3));
+}
+
+#[test]
+fn assemble_and_run() {
+    let source = "loadimm r1 <- #6\n\
+                  loadimm r2 <- #7\n\
+                  mul r3 <- r1, r2\n\
+                  not r4 <- r3\n\
+                  sltu r5 <- r3, r4\n\
+                  out_number r3\n\
+                  out_number r5\n\
+                  exit\n";
+    let program = assemble(source).unwrap();
+    assert_eq!(&[12, 3, 1, 2, 20, 4, 3][..], &program.bytes[8..15]);
+    let listing = disassemble(&program.bytes);
+    assert!(listing.contains("  0008   mul r3 <- r1, r2"));
+    assert!(listing.contains("  0012   not r4 <- r3"));
+    assert_eq!(program.bytes, assemble(&listing).unwrap().bytes);
+    let mut out = Vec::new();
+    Machine::new(&program.bytes).run_on(&mut out).unwrap();
+    assert_eq!(b"421", &out[..]);
+    assert!(assemble("add r1 <- r2 - r3").is_err());
+}
@@ -0,0 +1,122 @@
+use interpreter::{assemble, AssemblerError, Machine};
+
+macro_rules! check_listing {
+    ($name:ident, $dis:expr, $bin:expr) => {
+        #[test]
+        fn $name() {
+            let program = assemble(include_str!($dis)).unwrap();
+            assert_eq!(&include_bytes!($bin)[..], &program.bytes[..]);
+        }
+    };
+}
+
+// Every shipped listing must assemble to its prebuilt binary.
+check_listing!(listing_afact, "afact.dis", "afact.bin");
+check_listing!(listing_fact, "fact.dis", "fact.bin");
+check_listing!(listing_fibo, "fibo.dis", "fibo.bin");
+check_listing!(listing_function, "function.dis", "function.bin");
+check_listing!(listing_multiply, "multiply.dis", "multiply.bin");
+check_listing!(listing_push_pop, "push_pop.dis", "push_pop.bin");
+check_listing!(listing_rfact, "rfact.dis", "rfact.bin");
+check_listing!(listing_rfact_tr, "rfact_tr.dis", "rfact_tr.bin");
+check_listing!(
+    listing_99bottles,
+    "../examples/99bottles.dis",
+    "../examples/99bottles.bin"
+);
+check_listing!(
+    listing_count,
+    "../examples/count.dis",
+    "../examples/count.bin"
+);
+check_listing!(
+    listing_factorial,
+    "../examples/factorial.dis",
+    "../examples/factorial.bin"
+);
+check_listing!(
+    listing_fibonacci,
+    "../examples/fibonacci.dis",
+    "../examples/fibonacci.bin"
+);
+check_listing!(
+    listing_hello_world,
+    "../examples/hello_world.dis",
+    "../examples/hello_world.bin"
+);
+
+#[test]
+fn labels_are_resolved() {
+    let program = assemble(include_str!("rfact.dis")).unwrap();
+    assert_eq!(Some(87), program.label_address("rfact"));
+    assert_eq!(Some(187), program.label_address("ite_end_2"));
+    assert_eq!(Some(187), program.label_address("return_from_mult_1"));
+    assert_eq!(None, program.label_address("missing"));
+}
+
+#[test]
+fn source_without_address_column() {
+    let source = "
+        ; print 42 then a newline
+        loadimm r1 <- #42
+        out_number r1
+        loadimm r1 <- #nl      ; address of the data
+        load r1 <- [r1]
+        out r1
+        exit
+    nl:
+        b'\\n\\x00\\x00\\x00'
+    ";
+    let program = assemble(source).unwrap();
+    let mut machine = Machine::new(&program.bytes);
+    let mut out = Vec::new();
+    machine.run_on(&mut out).unwrap();
+    assert_eq!(&b"42\n"[..], &out[..]);
+}
+
+#[test]
+fn data_statements() {
+    let program = assemble("b'a;\\'b'\n[1, 0x2a, 255]\nb\"I'm\"").unwrap();
+    assert_eq!(&b"a;'b\x01\x2a\xffI'm"[..], &program.bytes[..]);
+}
+
+#[test]
+fn errors() {
+    assert!(matches!(
+        assemble("exit\nfrobnicate r1"),
+        Err(AssemblerError::Syntax { line: 2, .. })
+    ));
+    assert!(matches!(
+        assemble("sub r1 <- r2"),
+        Err(AssemblerError::Syntax { line: 1, .. })
+    ));
+    assert!(matches!(
+        assemble("loadimm r0 <- #nowhere"),
+        Err(AssemblerError::UndefinedLabel { line: 1, .. })
+    ));
+    assert!(matches!(
+        assemble("a:\nexit\na:"),
+        Err(AssemblerError::DuplicateLabel { line: 3, .. })
+    ));
+    assert!(matches!(
+        assemble("loadimm r1 <- #40000"),
+        Err(AssemblerError::ImmediateOutOfRange {
+            line: 1,
+            value: 40000
+        })
+    ));
+    for imm in ["#--5", "#-+5", "#+5", "#0x-5"] {
+        assert!(matches!(
+            assemble(&format!("loadimm r1 <- {}", imm)),
+            Err(AssemblerError::Syntax { line: 1, .. })
+        ));
+    }
+    assert!(matches!(
+        assemble("  0000   exit\n  0002   exit"),
+        Err(AssemblerError::AddressMismatch {
+            line: 2,
+            expected: 1,
+            found: 2
+        })
+    ));
+}
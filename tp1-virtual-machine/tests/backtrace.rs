@@ -0,0 +1,86 @@
+use interpreter::{Backtrace, Frame, Machine, MachineConfig, MachineError, Symbols};
+
+#[test]
+fn nested_calls() {
+    let symbols = Symbols::from_listing(include_str!("fibo.dis")).unwrap();
+    let fibo = symbols.address("fibo").unwrap();
+    let mut machine = Machine::new(include_bytes!("fibo.bin"));
+    machine.set_reg(10, 10).unwrap();
+    // Enter fibo(10), fibo(9) and fibo(8)
+    let mut entered = 0;
+    while entered < 3 {
+        machine.step_on(&mut Vec::new()).unwrap();
+        entered += (machine.regs()[0] == fibo) as u32;
+    }
+    let frames = machine.backtrace();
+    assert_eq!(3, frames.len());
+    assert_eq!(
+        Frame {
+            stack: 4092,
+            return_address: symbols.address("return_from_fibo_1").unwrap(),
+            call: 19,
+        },
+        frames[2]
+    );
+    let text = Backtrace {
+        ip: fibo,
+        frames: &frames,
+        symbols: Some(&symbols),
+    }
+    .to_string();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!("  #0 0024 fibo", lines[0]);
+    assert_eq!(
+        "  #3 0019, returning to return_from_fibo_1 [4092]",
+        lines[3]
+    );
+}
+
+#[test]
+fn step_limit() {
+    let mut machine = Machine::new(include_bytes!("rfact_native.bin"));
+    machine.set_reg(10, 5).unwrap();
+    let error = machine.run_with_limit(20).unwrap_err();
+    assert!(matches!(error, MachineError::StepLimitExceeded { .. }));
+    let ip = error.ip().unwrap();
+    assert_eq!(ip, machine.regs()[0]);
+    let frames = machine.backtrace();
+    assert!(!frames.is_empty());
+    // Without symbols, addresses are shown as is
+    let text = Backtrace {
+        ip,
+        frames: &frames,
+        symbols: None,
+    }
+    .to_string();
+    assert!(text.starts_with(&format!("  #0 {:04}\n", ip)));
+    assert_eq!(frames.len() + 1, text.lines().count());
+}
+
+#[test]
+fn empty_stack() {
+    let mut machine = Machine::new(&[]);
+    assert!(machine.backtrace().is_empty());
+    // An invalid instruction is not a call
+    machine.set_memory(0, &[255]).unwrap();
+    machine.set_reg(2, 4000).unwrap();
+    machine.set_memory(4000, &1u32.to_le_bytes()).unwrap();
+    assert!(machine.backtrace().is_empty());
+    assert_eq!(
+        None,
+        MachineError::InvalidSnapshot {
+            reason: String::new()
+        }
+        .ip()
+    );
+}
+
+#[test]
+fn no_stack_pointer() {
+    let config = MachineConfig {
+        memory_size: 4096,
+        registers: 2,
+    };
+    let machine = Machine::with_config(&[], config).unwrap();
+    assert!(machine.backtrace().is_empty());
+}
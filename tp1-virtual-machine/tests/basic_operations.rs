@@ -1,4 +1,4 @@
-use interpreter::Machine;
+use interpreter::{Machine, MachineError};
 use std::io::{self, Write};
 
 #[test]
@@ -147,7 +147,7 @@ fn test_store() {
     let mut machine = Machine::new(&[2, 0, 1]);
     machine.set_reg(1, 0x01020304).unwrap();
     expect(&mut machine, false, 3);
-    assert_eq!(&[04, 03, 02, 01], &machine.memory()[3..7]);
+    assert_eq!(&[4, 3, 2, 1], &machine.memory()[3..7]);
 }
 
 #[test]
@@ -291,8 +291,8 @@ fn no_wraparound_past_end_of_memory() {
     // 1:
     let mut memory = Machine::new(&[]).memory().to_vec();
     let memory_size = memory.len();
-    for i in memory_size - 4..memory_size {
-        memory[i] = 1;
+    for b in &mut memory[memory_size - 4..] {
+        *b = 1;
     }
     memory[0] = 7;
     let mut machine = Machine::new(&memory);
@@ -381,3 +381,36 @@ fn sub_with_wraparound_neg() {
     expect(&mut machine, false, 4);
     assert_eq!(machine.regs()[1], 2113797824);
 }
+
+#[test]
+fn test_set_memory() {
+    let mut machine = Machine::new(&[]);
+    let memory_size = machine.memory().len();
+    machine.set_memory(10, &[1, 2, 3]).unwrap();
+    assert_eq!(&[1, 2, 3], &machine.memory()[10..13]);
+    machine.set_memory(memory_size - 2, &[4, 5]).unwrap();
+    assert_eq!(&[4, 5], &machine.memory()[memory_size - 2..]);
+    assert!(machine.set_memory(memory_size - 1, &[6, 7]).is_err());
+    assert!(machine.set_memory(usize::MAX, &[8]).is_err());
+}
+
+#[test]
+fn run_with_limit() {
+    // 0: move r0 <- r1 if r2 != 0
+    // 4: exit
+    let mut machine = Machine::new(&[1, 0, 1, 2, 7]);
+    machine.set_reg(2, 1).unwrap();
+    match machine.run_with_limit(10) {
+        Err(MachineError::StepLimitExceeded { steps: 10, ip: 0 }) => (),
+        _ => panic!(),
+    }
+
+    // 0: sub r1 <- r1 - r0
+    // 4: exit
+    let mut machine = Machine::new(&[5, 1, 1, 0, 7]);
+    let mut out = Vec::new();
+    machine.run_on_with_limit(&mut out, 2).unwrap();
+    assert_eq!(5, machine.regs()[0]);
+    let mut machine = Machine::new(&[5, 1, 1, 0, 7]);
+    assert!(machine.run_on_with_limit(&mut out, 1).is_err());
+}
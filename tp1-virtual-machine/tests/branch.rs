@@ -0,0 +1,77 @@
+use interpreter::{assemble, disassemble, AssemblerError, Instruction, Machine};
+
+// Print the numbers from r1 down to 1, using relative branches only.
+const COUNTDOWN: &str = "\
+    loadimm r3 <- #1
+loop:
+    bz r1, #end
+    out_number r1
+    sub r1 <- r1 - r3
+    jmp #loop
+end:
+    exit
+";
+
+#[test]
+fn branches() {
+    let mut machine = Machine::new(&[]);
+    let mut out = Vec::new();
+    machine.set_reg(0, 100).unwrap();
+    machine
+        .execute_on(Instruction::Jump(-10), &mut out)
+        .unwrap();
+    assert_eq!(90, machine.regs()[0]);
+    machine
+        .execute_on(Instruction::BranchNonZero(1, 20), &mut out)
+        .unwrap();
+    assert_eq!(90, machine.regs()[0]);
+    machine
+        .execute_on(Instruction::BranchZero(1, 20), &mut out)
+        .unwrap();
+    assert_eq!(110, machine.regs()[0]);
+    // The IP wraps around
+    machine.set_reg(0, 0).unwrap();
+    machine.execute_on(Instruction::Jump(-1), &mut out).unwrap();
+    assert_eq!(u32::MAX, machine.regs()[0]);
+}
+
+#[test]
+fn assemble_offsets() {
+    let program = assemble(COUNTDOWN).unwrap();
+    // bz at 4 skips to 17, jmp at 14 goes back to 4
+    assert_eq!(&[31, 1, 9, 0][..], &program.bytes[4..8]);
+    assert_eq!(&[30, 0xf3, 0xff][..], &program.bytes[14..17]);
+    // Numeric immediates are offsets
+    assert_eq!(vec![30, 0xfd, 0xff], assemble("jmp #-3").unwrap().bytes);
+    let far = format!("jmp #far\nb'{}'\nfar:\nexit\n", "x".repeat(40000));
+    assert!(matches!(
+        assemble(&far),
+        Err(AssemblerError::ImmediateOutOfRange {
+            line: 1,
+            value: 40000
+        })
+    ));
+}
+
+#[test]
+fn disassemble_branches() {
+    let program = assemble(COUNTDOWN).unwrap();
+    let listing = disassemble(&program.bytes);
+    assert!(listing.contains("  0004   bz r1, #loc_0017"));
+    assert!(listing.contains("  0014   jmp #loc_0004"));
+    assert_eq!(program.bytes, assemble(&listing).unwrap().bytes);
+}
+
+#[test]
+fn relocated_program() {
+    let program = assemble(COUNTDOWN).unwrap();
+    for base in [0, 1000, 3000] {
+        let mut machine = Machine::new(&[]);
+        machine.set_memory(base, &program.bytes).unwrap();
+        machine.set_reg(0, base as u32).unwrap();
+        machine.set_reg(1, 3).unwrap();
+        let mut out = Vec::new();
+        machine.run_on(&mut out).unwrap();
+        assert_eq!(b"321", &out[..]);
+    }
+}
@@ -0,0 +1,45 @@
+use interpreter::assemble;
+use std::process::Command;
+
+// Return from a function to an address outside the memory.
+const BAD_RETURN: &str = "\
+    loadimm r2 <- #4096
+    call #f
+    exit
+f:
+    loadimm r1 <- #5000
+    push r1
+    ret
+";
+
+#[test]
+fn bad_return_backtrace() {
+    let program = assemble(BAD_RETURN).unwrap();
+    let image = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("bad_return.bin");
+    std::fs::write(&image, &program.bytes).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_tp-rust-2"))
+        .arg(&image)
+        .output()
+        .unwrap();
+    assert_eq!(Some(10), output.status.code());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<&str> = stderr.lines().skip(1).collect();
+    assert_eq!(
+        [
+            "backtrace:",
+            "  #0 5000",
+            "  #1 0004, returning to 0007 [4092]"
+        ],
+        &lines[..]
+    );
+}
+
+#[test]
+fn resume_with_protection() {
+    // The regions come from the snapshot
+    let output = Command::new(env!("CARGO_BIN_EXE_tp-rust-2"))
+        .args(["--protect", "--resume", "state.snap"])
+        .output()
+        .unwrap();
+    assert_eq!(Some(2), output.status.code());
+}
@@ -0,0 +1,32 @@
+// Each test file uses its own part of these helpers
+#![allow(dead_code)]
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+// A buffer which can be inspected while a machine, a device or a tracer
+// owns a handle to it.
+#[derive(Clone, Default)]
+pub struct Shared(pub Rc<RefCell<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Shared {
+    // Lines written so far, without their surrounding spaces.
+    pub fn lines(&self) -> Vec<String> {
+        let text = String::from_utf8(self.0.borrow().clone()).unwrap();
+        text.lines().map(|line| line.trim().to_string()).collect()
+    }
+}
+
+// Registers to set before running, with their values
+pub type Args<'a> = &'a [(usize, u32)];
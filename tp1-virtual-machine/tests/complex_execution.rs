@@ -1,9 +1,13 @@
 use interpreter::Machine;
 
+// Generous bound on the number of executed instructions, so that a broken
+// program fails instead of hanging the test suite.
+const MAX_STEPS: u64 = 10_000_000;
+
 #[test]
 fn test_push_pop() {
     let mut machine = Machine::new(include_bytes!("push_pop.bin"));
-    machine.run().unwrap();
+    machine.run_with_limit(MAX_STEPS).unwrap();
     assert_eq!(26, machine.regs()[1]);
     assert_eq!(15, machine.regs()[2]);
 }
@@ -11,7 +15,7 @@ fn test_push_pop() {
 #[test]
 fn test_function() {
     let mut machine = Machine::new(include_bytes!("function.bin"));
-    machine.run().unwrap();
+    machine.run_with_limit(MAX_STEPS).unwrap();
     assert_eq!(42, machine.regs()[10]);
 }
 
@@ -24,7 +28,7 @@ fn test_mult() {
             let mut machine = Machine::new(include_bytes!("multiply.bin"));
             machine.set_reg(11, *left as u32).unwrap();
             machine.set_reg(12, *right as u32).unwrap();
-            machine.run().unwrap();
+            machine.run_with_limit(MAX_STEPS).unwrap();
             assert_eq!(*left * *right, machine.regs()[11] as i32);
         }
     }
@@ -40,7 +44,7 @@ fn test_fact() {
     for i in 1..13 {
         let mut machine = Machine::new(include_bytes!("fact.bin"));
         machine.set_reg(10, i).unwrap();
-        machine.run().unwrap();
+        machine.run_with_limit(MAX_STEPS).unwrap();
         assert_eq!(fact(i), machine.regs()[11]);
     }
 }
@@ -51,7 +55,7 @@ fn test_afact() {
     for i in 1..13 {
         let mut machine = Machine::new(include_bytes!("afact.bin"));
         machine.set_reg(10, i).unwrap();
-        machine.run().unwrap();
+        machine.run_with_limit(MAX_STEPS).unwrap();
         assert_eq!(fact(i), machine.regs()[11]);
     }
 }
@@ -62,7 +66,7 @@ fn test_rfact() {
     for i in 1..13 {
         let mut machine = Machine::new(include_bytes!("rfact.bin"));
         machine.set_reg(10, i).unwrap();
-        machine.run().unwrap();
+        machine.run_with_limit(MAX_STEPS).unwrap();
         assert_eq!(fact(i), machine.regs()[11]);
     }
 }
@@ -73,7 +77,7 @@ fn test_rfact_tr() {
     for i in 1..13 {
         let mut machine = Machine::new(include_bytes!("rfact_tr.bin"));
         machine.set_reg(10, i).unwrap();
-        machine.run().unwrap();
+        machine.run_with_limit(MAX_STEPS).unwrap();
         assert_eq!(fact(i), machine.regs()[11]);
     }
 }
@@ -87,7 +91,7 @@ fn test_fibo() {
     for i in 1..20 {
         let mut machine = Machine::new(include_bytes!("fibo.bin"));
         machine.set_reg(10, i).unwrap();
-        machine.run().unwrap();
+        machine.run_with_limit(MAX_STEPS).unwrap();
         assert_eq!(fibo(i), machine.regs()[11]);
     }
 }
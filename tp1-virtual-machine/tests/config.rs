@@ -0,0 +1,109 @@
+use interpreter::{assemble, Instruction, Machine, MachineConfig, MachineError};
+
+#[test]
+fn default_config() {
+    let machine = Machine::new(&[]);
+    assert_eq!(MachineConfig::default(), machine.config());
+    assert_eq!(4096, machine.memory().len());
+    assert_eq!(16, machine.regs().len());
+}
+
+#[test]
+fn larger_memory() {
+    let config = MachineConfig {
+        memory_size: 65536,
+        registers: 16,
+    };
+    let image = vec![0; 5000];
+    let mut machine = Machine::with_config(&image, config).unwrap();
+    assert_eq!(config, machine.config());
+    assert_eq!(65536, machine.memory().len());
+
+    // Store beyond the default memory size
+    machine.set_reg(1, 60000).unwrap();
+    machine.set_reg(2, 0x01020304).unwrap();
+    machine.execute(Instruction::Store(1, 2)).unwrap();
+    assert_eq!(&[4, 3, 2, 1], &machine.memory()[60000..60004]);
+}
+
+#[test]
+fn image_too_large() {
+    let config = MachineConfig {
+        memory_size: 16,
+        registers: 16,
+    };
+    assert!(matches!(
+        Machine::with_config(&[0; 17], config),
+        Err(MachineError::ImageTooLarge {
+            size: 17,
+            memory_size: 16
+        })
+    ));
+}
+
+#[test]
+fn memory_too_large() {
+    let config = MachineConfig {
+        memory_size: (1 << 32) + 1,
+        registers: 16,
+    };
+    assert!(matches!(
+        Machine::with_config(&[], config),
+        Err(MachineError::MemoryTooLarge {
+            memory_size: 0x1_0000_0001
+        })
+    ));
+}
+
+#[test]
+fn tiny_profile() {
+    // A small program still runs with 256 bytes of memory and 4 registers,
+    // and the extra registers are refused.
+    let program = assemble(
+        "
+        loadimm r1 <- #-3
+        loadimm r2 <- #4
+        sub r3 <- r2 - r1
+        out_number r3
+        exit
+        ",
+    )
+    .unwrap();
+    let config = MachineConfig {
+        memory_size: 256,
+        registers: 4,
+    };
+    let mut machine = Machine::with_config(&program.bytes, config).unwrap();
+    let mut out = Vec::new();
+    machine.run_on(&mut out).unwrap();
+    assert_eq!(&b"7"[..], &out[..]);
+
+    let mut machine = Machine::with_config(&[8, 4], config).unwrap();
+    assert!(matches!(
+        machine.step(),
+        Err(MachineError::InvalidRegisterAccess { register: 4, .. })
+    ));
+    assert!(machine.set_reg(4, 0).is_err());
+}
+
+#[test]
+fn more_registers() {
+    let config = MachineConfig {
+        memory_size: 4096,
+        registers: 256,
+    };
+    // 0: loadimm r255 <- #42
+    let mut machine = Machine::with_config(&[4, 255, 42, 0], config).unwrap();
+    machine.step().unwrap();
+    assert_eq!(42, machine.regs()[255]);
+}
+
+#[test]
+#[should_panic]
+fn no_registers() {
+    let config = MachineConfig {
+        memory_size: 4096,
+        registers: 0,
+    };
+    let _ = Machine::with_config(&[], config);
+}
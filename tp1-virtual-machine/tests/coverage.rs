@@ -0,0 +1,73 @@
+use interpreter::{Coverage, Machine};
+
+// Coverage of a program run with each input in r10, as in the
+// complex_execution tests.
+fn run_all(image: &[u8], inputs: impl IntoIterator<Item = u32>) -> Coverage {
+    let mut coverage = Coverage::new();
+    for input in inputs {
+        let mut machine = Machine::new(image);
+        machine.set_reg(10, input).unwrap();
+        machine.set_coverage(Some(coverage));
+        machine.run_on(&mut Vec::new()).unwrap();
+        coverage = machine.take_coverage().unwrap();
+    }
+    coverage
+}
+
+#[test]
+fn untested_branches() {
+    let listing = include_str!("fact.dis");
+    let coverage = run_all(include_bytes!("fact.bin"), [1]);
+    assert_eq!((18, 43), coverage.totals(listing));
+    let report = coverage.report(listing);
+    assert!(report.starts_with("18 of 43 instructions executed (41.9%)\n"));
+    assert!(report.contains("\n  fact_loop              5/5\n  ite_then_2             0/6\n"));
+    assert!(report.contains("\n  38:   0111   move r12 <- r10 if r0 != 0\n"));
+
+    // Every instruction runs with larger inputs
+    let complete = run_all(include_bytes!("fact.bin"), 1..13);
+    assert_eq!((43, 43), complete.totals(listing));
+    assert!(!complete.report(listing).contains("not executed"));
+}
+
+#[test]
+fn merged_runs() {
+    let image = include_bytes!("fibo.bin");
+    let mut merged = run_all(image, [1]);
+    merged.merge(&run_all(image, [5, 10]));
+    assert_eq!(run_all(image, [1, 5, 10]), merged);
+    assert_eq!(3, merged.hits(0));
+
+    // The jumps following the returns of fibo are dead code
+    let report = merged.report(include_str!("fibo.dis"));
+    assert!(report.ends_with(
+        "not executed:\n  \
+         18:   0055   loadimm r0 <- #ite_end_1\n  \
+         31:   0098   loadimm r0 <- #ite_end_2\n"
+    ));
+}
+
+#[test]
+fn lcov() {
+    let coverage = run_all(include_bytes!("fact.bin"), [1]);
+    let lcov = coverage.lcov("tests/fact.dis", include_str!("fact.dis"));
+    let lines: Vec<&str> = lcov.lines().collect();
+    assert_eq!(
+        ["TN:", "SF:tests/fact.dis", "FN:7,return_from_fact_1"],
+        lines[..3]
+    );
+    assert!(lines.contains(&"FN:37,ite_then_2"));
+    assert!(lines.contains(&"FNDA:0,ite_then_2"));
+    assert!(lines.contains(&"FNDA:1,fact_loop"));
+    assert!(lines.contains(&"DA:1,1"));
+    assert!(lines.contains(&"DA:38,0"));
+    assert!(lines.contains(&"FNF:10"));
+    assert_eq!(
+        ["FNH:4", "DA:1,1"],
+        lines[lines.iter().position(|&l| l == "FNH:4").unwrap()..][..2]
+    );
+    assert_eq!(
+        ["LF:43", "LH:18", "end_of_record"],
+        lines[lines.len() - 3..]
+    );
+}
@@ -0,0 +1,64 @@
+use interpreter::Machine;
+
+mod common;
+use common::Args;
+
+// Run `image` with the given registers set, returning the output and the
+// final machine.
+fn run(image: &[u8], args: Args, cached: bool) -> (Vec<u8>, Machine) {
+    let mut machine = Machine::new(image);
+    machine.set_decode_cache(cached);
+    for &(reg, value) in args {
+        machine.set_reg(reg, value).unwrap();
+    }
+    let mut out = Vec::new();
+    machine.run_on(&mut out).unwrap();
+    (out, machine)
+}
+
+#[test]
+fn same_results() {
+    let programs: [(&[u8], Args); 4] = [
+        (include_bytes!("fibo.bin"), &[(10, 12)]),
+        (include_bytes!("multiply.bin"), &[(11, 7), (12, 500)]),
+        (include_bytes!("rfact_native.bin"), &[(10, 6)]),
+        (include_bytes!("../examples/99bottles.bin"), &[]),
+    ];
+    for (image, args) in programs {
+        let (out, machine) = run(image, args, false);
+        let (cached_out, cached) = run(image, args, true);
+        assert_eq!(out, cached_out);
+        assert_eq!(machine.regs(), cached.regs());
+        assert_eq!(machine.memory(), cached.memory());
+    }
+}
+
+#[test]
+fn self_modifying_code() {
+    // Print the immediate of a loadimm r1 times, rewriting it as
+    // `loadimm r6 <- #7` after the first time
+    for cached in [false, true] {
+        let (out, _) = run(include_bytes!("patched.bin"), &[(1, 3)], cached);
+        assert_eq!(b"077", &out[..]);
+    }
+}
+
+#[test]
+fn host_changes() {
+    // 0: out_number r1, 2: exit
+    let mut machine = Machine::new(&[8, 1, 7]);
+    machine.set_decode_cache(true);
+    machine.set_reg(1, 5).unwrap();
+    let snapshot = machine.snapshot();
+    let mut out = Vec::new();
+    machine.run_on(&mut out).unwrap();
+    // out_number r2 instead
+    machine.set_memory(1, &[2]).unwrap();
+    machine.set_reg(0, 0).unwrap();
+    machine.set_reg(2, 9).unwrap();
+    machine.run_on(&mut out).unwrap();
+    // And back to the original code
+    machine.restore(&snapshot).unwrap();
+    machine.run_on(&mut out).unwrap();
+    assert_eq!(b"595", &out[..]);
+}
@@ -0,0 +1,132 @@
+use interpreter::{
+    assemble, Console, Device, Machine, MachineError, Timer, CONSOLE_BASE, TIMER_BASE,
+};
+
+mod common;
+use common::Shared;
+
+// Print a number then echo the input, through the console.
+const ECHO: &str = "\
+    loadimm r1 <- #-4096
+    loadimm r2 <- #4
+    add r2 <- r1, r2
+    loadimm r3 <- #-42
+    store [r2] <- r3
+    loadimm r4 <- #1
+loop:
+    load r3 <- [r1]
+    add r5 <- r3, r4
+    bz r5, #end
+    store [r1] <- r3
+    jmp #loop
+end:
+    exit
+";
+
+#[test]
+fn console() {
+    let out = Shared::default();
+    let mut machine = Machine::new(&assemble(ECHO).unwrap().bytes);
+    machine.attach(
+        CONSOLE_BASE,
+        Box::new(Console::new(out.clone()).with_input(&b"Hi!\n"[..])),
+    );
+    machine.run_on(&mut Vec::new()).unwrap();
+    assert_eq!(b"-42Hi!\n", &out.0.borrow()[..]);
+}
+
+#[test]
+fn timer() {
+    // 0: load r1 <- [r2], 3: store [r3] <- r4, 6..: loads
+    let mut machine = Machine::new(&[3, 1, 2, 2, 3, 4, 3, 1, 2, 3, 1, 2, 3, 5, 6]);
+    machine.attach(TIMER_BASE, Box::new(Timer::new()));
+    machine.set_reg(2, TIMER_BASE).unwrap();
+    machine.set_reg(3, TIMER_BASE + 4).unwrap();
+    machine.set_reg(4, 3).unwrap();
+    machine.set_reg(6, TIMER_BASE + 8).unwrap();
+    machine.step_on(&mut Vec::new()).unwrap();
+    assert_eq!(0, machine.regs()[1]);
+    // The count includes the store setting the limit
+    machine.step_on(&mut Vec::new()).unwrap();
+    machine.step_on(&mut Vec::new()).unwrap();
+    assert_eq!(2, machine.regs()[1]);
+    machine.step_on(&mut Vec::new()).unwrap();
+    assert_eq!(3, machine.regs()[1]);
+    machine.step_on(&mut Vec::new()).unwrap();
+    assert_eq!(1, machine.regs()[5]);
+}
+
+#[test]
+fn invalid_device_accesses() {
+    // 0: load r1 <- [r2]
+    let mut machine = Machine::new(&[3, 1, 2]);
+    machine.attach(TIMER_BASE, Box::new(Timer::new()));
+    for address in [
+        TIMER_BASE + 12,
+        TIMER_BASE + 10,
+        TIMER_BASE - 4,
+        0x8000_0000,
+    ] {
+        machine.set_reg(0, 0).unwrap();
+        machine.set_reg(2, address).unwrap();
+        let error = machine.step_on(&mut Vec::new()).unwrap_err();
+        assert_eq!(
+            format!(
+                "invalid memory access of 4 byte(s) at address {:#x} by instruction 3 at 0000",
+                address
+            ),
+            error.to_string()
+        );
+    }
+}
+
+// A device made of eight scratch words
+#[derive(Default)]
+struct Scratch([u32; 8]);
+
+impl Device for Scratch {
+    fn size(&self) -> u32 {
+        32
+    }
+
+    fn read32(&mut self, offset: u32) -> Result<u32, MachineError> {
+        Ok(self.0[offset as usize / 4])
+    }
+
+    fn write32(&mut self, offset: u32, value: u32) -> Result<(), MachineError> {
+        self.0[offset as usize / 4] = value;
+        Ok(())
+    }
+}
+
+#[test]
+fn custom_device() {
+    let program = assemble(
+        "loadimm r1 <- #-32\n\
+         loadimm r2 <- #1234\n\
+         store [r1] <- r2\n\
+         load r3 <- [r1]\n\
+         exit",
+    )
+    .unwrap();
+    let mut machine = Machine::new(&program.bytes);
+    machine.attach(0xffff_ffe0, Box::<Scratch>::default());
+    machine.run_on(&mut Vec::new()).unwrap();
+    assert_eq!(1234, machine.regs()[3]);
+    // Devices are not part of the memory
+    assert_eq!(4096, machine.memory().len());
+}
+
+#[test]
+#[should_panic(expected = "overlaps another device")]
+fn overlapping_devices() {
+    let mut machine = Machine::new(&[]);
+    machine.attach(0x10000, Box::<Scratch>::default());
+    machine.attach(0x10010, Box::new(Timer::new()));
+}
+
+#[test]
+#[should_panic(expected = "does not fit above the RAM")]
+fn device_in_memory() {
+    Machine::new(&[]).attach(4000, Box::new(Timer::new()));
+}
@@ -0,0 +1,90 @@
+use interpreter::{assemble, disassemble};
+
+// Remove label definitions and replace label references by their address,
+// so that listings using different label names can be compared.
+fn normalize(listing: &str) -> Vec<String> {
+    let program = assemble(listing).unwrap();
+    listing
+        .lines()
+        .filter(|line| !line.ends_with(':'))
+        .map(|line| match line.split_once('#') {
+            Some((head, label)) => match program.label_address(label) {
+                Some(address) => format!("{}#{}", head, address),
+                None => line.to_string(),
+            },
+            None => line.to_string(),
+        })
+        .collect()
+}
+
+macro_rules! check_listing {
+    ($name:ident, $dis:expr, $bin:expr) => {
+        #[test]
+        fn $name() {
+            let image = include_bytes!($bin);
+            let listing = disassemble(image);
+            // The listing can be assembled back into the same image...
+            assert_eq!(&image[..], &assemble(&listing).unwrap().bytes[..]);
+            // ...and only differs from the shipped one by its label names.
+            assert_eq!(normalize(include_str!($dis)), normalize(&listing));
+        }
+    };
+}
+
+check_listing!(listing_afact, "afact.dis", "afact.bin");
+check_listing!(listing_fact, "fact.dis", "fact.bin");
+check_listing!(listing_fibo, "fibo.dis", "fibo.bin");
+check_listing!(listing_function, "function.dis", "function.bin");
+check_listing!(listing_multiply, "multiply.dis", "multiply.bin");
+check_listing!(listing_push_pop, "push_pop.dis", "push_pop.bin");
+check_listing!(listing_rfact, "rfact.dis", "rfact.bin");
+check_listing!(listing_rfact_tr, "rfact_tr.dis", "rfact_tr.bin");
+check_listing!(
+    listing_99bottles,
+    "../examples/99bottles.dis",
+    "../examples/99bottles.bin"
+);
+check_listing!(
+    listing_count,
+    "../examples/count.dis",
+    "../examples/count.bin"
+);
+check_listing!(
+    listing_factorial,
+    "../examples/factorial.dis",
+    "../examples/factorial.bin"
+);
+check_listing!(
+    listing_fibonacci,
+    "../examples/fibonacci.dis",
+    "../examples/fibonacci.bin"
+);
+check_listing!(
+    listing_hello_world,
+    "../examples/hello_world.dis",
+    "../examples/hello_world.bin"
+);
+
+#[test]
+fn synthetic_labels() {
+    let listing = disassemble(include_bytes!("function.bin"));
+    assert!(listing.contains("  0012   loadimm r3 <- #loc_0023\n"));
+    assert!(listing.contains("  0019   loadimm r0 <- #loc_0024\nloc_0023:\n  0023   exit\n"));
+}
+
+#[test]
+fn unreachable_bytes_are_data() {
+    // 0: exit
+    // 1: 0, 0xff, 7
+    let listing = disassemble(&[7, 0, 0xff, 7]);
+    assert_eq!("  0000   exit\n  ???? [0, 255, 7]\n", listing);
+
+    // 0: loadimm r1 <- #data_0005
+    // 4: exit
+    // 5: b"it's"
+    let listing = disassemble(&[4, 1, 5, 0, 7, b'i', b't', b'\'', b's']);
+    assert_eq!(
+        "  0000   loadimm r1 <- #data_0005\n  0004   exit\ndata_0005:\n  ???? b\"it's\"\n",
+        listing
+    );
+}
@@ -0,0 +1,158 @@
+use interpreter::{Instruction, Machine, MachineError};
+
+#[test]
+fn invalid_instruction() {
+    // 0: exit
+    // 1: invalid
+    let mut machine = Machine::new(&[7, 42]);
+    machine.set_reg(0, 1).unwrap();
+    let error = machine.step().unwrap_err();
+    assert!(matches!(
+        error,
+        MachineError::InvalidInstruction { ip: 1, opcode: 42 }
+    ));
+    assert_eq!("invalid instruction 42 at 0001", error.to_string());
+}
+
+#[test]
+fn invalid_register() {
+    // 0: sub r1 <- r20 - r2
+    let mut machine = Machine::new(&[5, 1, 20, 2]);
+    let error = machine.step().unwrap_err();
+    assert!(matches!(
+        error,
+        MachineError::InvalidRegisterAccess {
+            ip: 0,
+            opcode: Some(5),
+            register: 20,
+            host: false
+        }
+    ));
+    assert_eq!(
+        "invalid register r20 used by instruction 5 at 0000",
+        error.to_string()
+    );
+    // The IP has not moved
+    assert_eq!(0, machine.regs()[0]);
+
+    let error = machine.set_reg(16, 0).unwrap_err();
+    assert!(matches!(
+        error,
+        MachineError::InvalidRegisterAccess {
+            opcode: None,
+            register: 16,
+            host: true,
+            ..
+        }
+    ));
+    assert_eq!("invalid register r16", error.to_string());
+    assert_eq!(None, error.ip());
+}
+
+#[test]
+fn invalid_data_access() {
+    // 0: loadimm r1 <- #4094
+    // 4: store [r1] <- r2
+    let mut machine = Machine::new(&[4, 1, 0xfe, 0x0f, 2, 1, 2]);
+    machine.step().unwrap();
+    let error = machine.step().unwrap_err();
+    assert!(matches!(
+        error,
+        MachineError::InvalidMemoryAccess {
+            ip: 4,
+            opcode: Some(2),
+            address: 4094,
+            width: 4,
+            host: false
+        }
+    ));
+    assert_eq!(
+        "invalid memory access of 4 byte(s) at address 0xffe by instruction 2 at 0004",
+        error.to_string()
+    );
+    assert_eq!(Some(4), error.ip());
+
+    let error = machine.set_memory(4095, &[1, 2]).unwrap_err();
+    assert!(matches!(
+        error,
+        MachineError::InvalidMemoryAccess { host: true, .. }
+    ));
+    assert_eq!(None, error.ip());
+}
+
+#[test]
+fn invalid_fetch() {
+    // end-2: sub r1 <- r1 - r1 (truncated)
+    let memory_size = Machine::new(&[]).memory().len();
+    let mut memory = vec![0; memory_size - 2];
+    memory.extend([5, 1]);
+    let mut machine = Machine::new(&memory);
+    machine.set_reg(0, memory_size as u32 - 2).unwrap();
+    assert!(matches!(
+        machine.step(),
+        Err(MachineError::InvalidMemoryAccess {
+            ip,
+            opcode: Some(5),
+            address,
+            width: 4,
+            host: false
+        }) if ip == memory_size as u32 - 2 && address == ip
+    ));
+
+    machine.set_reg(0, 0xFFFF_FFFF).unwrap();
+    let error = machine.step().unwrap_err();
+    assert!(matches!(
+        error,
+        MachineError::InvalidMemoryAccess {
+            ip: 0xFFFF_FFFF,
+            opcode: None,
+            address: 0xFFFF_FFFF,
+            width: 1,
+            host: false
+        }
+    ));
+    // The program ran off the end of the memory
+    assert_eq!(Some(0xFFFF_FFFF), error.ip());
+}
+
+#[test]
+fn bad_return_address() {
+    // 0: loadimm r2 <- #4096, 4: loadimm r1 <- #5000, 8: push r1, 10: ret
+    let mut machine = Machine::new(&[4, 2, 0, 16, 4, 1, 0x88, 0x13, 26, 1, 29]);
+    let error = machine.run_on(&mut Vec::new()).unwrap_err();
+    assert!(matches!(
+        error,
+        MachineError::InvalidMemoryAccess {
+            address: 5000,
+            host: false,
+            ..
+        }
+    ));
+    assert_eq!(Some(5000), error.ip());
+}
+
+#[test]
+fn execute_reports_the_instruction_address() {
+    // The IP is expected to point after the executed instruction
+    let mut machine = Machine::new(&[]);
+    machine.set_reg(0, 100).unwrap();
+    machine.set_reg(1, 5000).unwrap();
+    assert!(matches!(
+        machine.execute(Instruction::Load(2, 1)),
+        Err(MachineError::InvalidMemoryAccess {
+            ip: 97,
+            opcode: Some(3),
+            address: 5000,
+            width: 4,
+            host: false
+        })
+    ));
+}
+
+#[test]
+fn io_error_source() {
+    use std::error::Error;
+    let error = MachineError::from(std::io::Error::other("broken pipe"));
+    assert!(error.source().is_some());
+    assert_eq!("I/O error: broken pipe", error.to_string());
+}
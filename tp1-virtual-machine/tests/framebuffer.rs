@@ -0,0 +1,52 @@
+use interpreter::{Device, Framebuffer, Machine, FRAMEBUFFER_BASE, FRAME_SIZE};
+
+mod common;
+use common::Shared;
+
+#[test]
+fn diagonal() {
+    let out = Shared::default();
+    let mut machine = Machine::new(include_bytes!("../examples/diagonal.bin"));
+    machine.attach(FRAMEBUFFER_BASE, Box::new(Framebuffer::new(out.clone())));
+    machine.run_on(&mut Vec::new()).unwrap();
+    let records = out.0.borrow();
+    assert_eq!(8 * (FRAME_SIZE + 1), records.len());
+    for (k, record) in records.chunks(FRAME_SIZE + 1).enumerate() {
+        assert_eq!(0xff, record[0]);
+        // Only the red byte of pixel (k, 7 - k) is lit
+        let lit = 3 * (8 * k + 7 - k);
+        for (i, &b) in record[1..].iter().enumerate() {
+            assert_eq!(if i == lit { 0xfe } else { 0 }, b);
+        }
+    }
+}
+
+// The records have the format understood by the LED matrix board.
+#[test]
+fn se203_record() {
+    let one_frame = include_bytes!("../../tp2-led-matrix/one_frame.bin");
+    let out = Shared::default();
+    let mut framebuffer = Framebuffer::new(out.clone());
+    for (offset, word) in one_frame[1..].chunks(4).enumerate() {
+        let value = u32::from_le_bytes(word.try_into().unwrap());
+        framebuffer.write32(4 * offset as u32, value).unwrap();
+    }
+    assert_eq!(0, framebuffer.read32(FRAME_SIZE as u32).unwrap());
+    framebuffer.write32(FRAME_SIZE as u32, 0).unwrap();
+    assert_eq!(1, framebuffer.read32(FRAME_SIZE as u32).unwrap());
+    assert_eq!(&one_frame[..], &out.0.borrow()[..]);
+}
+
+#[test]
+fn saturated_pixels() {
+    let out = Shared::default();
+    let mut framebuffer = Framebuffer::new(out.clone());
+    framebuffer.write32(188, 0xffff_ffff).unwrap();
+    assert_eq!(0xffff_ffff, framebuffer.read32(188).unwrap());
+    assert!(framebuffer.write32(189, 0).is_err());
+    framebuffer.write32(FRAME_SIZE as u32, 0).unwrap();
+    let record = out.0.borrow();
+    // The record marker is the only 0xff byte
+    assert_eq!(&[0xfe; 4], &record[189..]);
+    assert_eq!(1, record.iter().filter(|&&b| b == 0xff).count());
+}
@@ -0,0 +1,116 @@
+use interpreter::{
+    assemble, Machine, MachineError, Permissions, Region, Timer, TIMER_BASE, TRAP_IP, TRAP_VECTOR,
+};
+
+// Machine running fibo(n), with the decode cache if `cached`.
+fn fibo(n: u32, cached: bool) -> Machine {
+    let mut machine = Machine::new(include_bytes!("fibo.bin"));
+    machine.set_decode_cache(cached);
+    machine.set_reg(10, n).unwrap();
+    machine
+}
+
+#[test]
+fn step_limits() {
+    // Stopping in the middle of fused sequences leaves the same state
+    for max_steps in (1..300).chain([1000, 4722]) {
+        let mut plain = fibo(10, false);
+        let mut fused = fibo(10, true);
+        let plain_error = plain.run_on_with_limit(&mut Vec::new(), max_steps);
+        let fused_error = fused.run_on_with_limit(&mut Vec::new(), max_steps);
+        assert!(matches!(
+            (plain_error, fused_error),
+            (
+                Err(MachineError::StepLimitExceeded { ip: a, .. }),
+                Err(MachineError::StepLimitExceeded { ip: b, .. })
+            ) if a == b
+        ));
+        assert_eq!(plain.regs(), fused.regs());
+        assert_eq!(plain.memory(), fused.memory());
+    }
+    fibo(10, true)
+        .run_on_with_limit(&mut Vec::new(), 4723)
+        .unwrap();
+}
+
+// Machine which ran fibo(3) and is about to run fibo(0) again with the
+// return address of fibo unreadable, once its return has been fused.
+fn unreadable_return(cached: bool) -> Machine {
+    let mut machine = fibo(3, cached);
+    machine.run_on(&mut Vec::new()).unwrap();
+    machine.set_reg(0, 0).unwrap();
+    machine.set_reg(10, 0).unwrap();
+    let hidden = Permissions {
+        read: false,
+        ..Permissions::RW
+    };
+    machine.protect(Region::new("stack", 4092..4096, hidden));
+    machine
+}
+
+#[test]
+fn faulting_return() {
+    let mut errors = Vec::new();
+    for cached in [false, true] {
+        let mut machine = unreadable_return(cached);
+        let error = machine.run_on(&mut Vec::new()).unwrap_err();
+        errors.push((error.to_string(), machine.regs().to_vec()));
+    }
+    assert_eq!(
+        "read at address 0xffc denied by region stack at 0052",
+        errors[0].0
+    );
+    assert_eq!(errors[0], errors[1]);
+
+    // Trapped faults resume the same way
+    let mut machine = unreadable_return(true);
+    machine.set_control(TRAP_VECTOR, 23);
+    machine.run_on(&mut Vec::new()).unwrap();
+    assert_eq!(52, machine.controls()[TRAP_IP as usize]);
+    assert_eq!(4092, machine.regs()[3]);
+}
+
+// Count down r1 with fused sequences, while a timer interrupts every 7
+// instructions a handler counting in r9 with fused sequences as well.
+const TICKING: &str = "\
+    loadimm r12 <- #1
+    loadimm r13 <- #-4072   ; timer status
+    loadimm r14 <- #-4076   ; timer limit
+    loadimm r15 <- #7
+    store [r14] <- r15
+    loadimm r14 <- #handler
+    wrctl c4 <- r14
+    wrctl c6 <- r12         ; enable line 0, the timer
+    store [r13] <- r12      ; start counting from now
+loop:
+    loadimm r3 <- #1
+    sub r1 <- r1 - r3
+    loadimm r4 <- #loop
+    move r0 <- r4 if r1 != 0
+    exit
+handler:
+    loadimm r5 <- #-1
+    sub r9 <- r9 - r5
+    loadimm r5 <- #-1
+    sub r9 <- r9 - r5
+    store [r13] <- r12      ; acknowledge the timer
+    reti
+";
+
+#[test]
+fn devices() {
+    // Devices tick and interrupts are taken as when stepping
+    let program = assemble(TICKING).unwrap();
+    for max_steps in (1..200).chain([1000]) {
+        let mut snapshots = Vec::new();
+        for cached in [false, true] {
+            let mut machine = Machine::new(&program.bytes);
+            machine.attach(TIMER_BASE, Box::new(Timer::new()));
+            machine.set_decode_cache(cached);
+            machine.set_reg(1, 50).unwrap();
+            let result = machine.run_on_with_limit(&mut Vec::new(), max_steps);
+            snapshots.push((result.is_ok(), machine.snapshot()));
+        }
+        assert_eq!(snapshots[0], snapshots[1]);
+    }
+}
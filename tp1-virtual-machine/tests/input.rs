@@ -0,0 +1,68 @@
+use interpreter::{assemble, disassemble, Instruction, Machine, MachineError};
+
+#[test]
+fn read_bytes() {
+    let mut machine = Machine::new(&[]);
+    let mut input = &b"Hi"[..];
+    let mut out = Vec::new();
+    for expected in [b'H' as u32, b'i' as u32, u32::MAX, u32::MAX] {
+        machine
+            .execute_io(Instruction::In(1), &mut input, &mut out)
+            .unwrap();
+        assert_eq!(expected, machine.regs()[1]);
+    }
+    // Without an input, the end is reached immediately
+    machine.execute_on(Instruction::In(2), &mut out).unwrap();
+    assert_eq!(u32::MAX, machine.regs()[2]);
+}
+
+#[test]
+fn read_numbers() {
+    let mut machine = Machine::new(&[]);
+    let mut input = &b"  42\n-17 +3,0x"[..];
+    let mut out = Vec::new();
+    for expected in [42, -17, 3, 0] {
+        machine
+            .execute_io(Instruction::InNumber(1), &mut input, &mut out)
+            .unwrap();
+        assert_eq!(expected, machine.regs()[1] as i32);
+    }
+    // The character ending the number has been consumed
+    assert_eq!(b"", input);
+}
+
+#[test]
+fn invalid_number() {
+    // 0: in_number r1, with no digits to read
+    let mut machine = Machine::new(&[10, 1]);
+    let result = machine.step_io(&mut &b"  -x"[..], &mut Vec::new());
+    assert!(matches!(result, Err(MachineError::InvalidNumber { ip: 0 })));
+    assert_eq!(
+        "no number found in the input read at 0000",
+        result.unwrap_err().to_string()
+    );
+    // The end of the input is no number either
+    machine.set_reg(0, 0).unwrap();
+    let result = machine.step_on(&mut Vec::new());
+    assert!(matches!(result, Err(MachineError::InvalidNumber { ip: 0 })));
+}
+
+#[test]
+fn run_interactive() {
+    let program = assemble(
+        "in_number r1\n\
+         in_number r2\n\
+         sub r3 <- r1 - r2\n\
+         out_number r3\n\
+         in r4\n\
+         out r4\n\
+         exit\n",
+    )
+    .unwrap();
+    assert_eq!(&[9, 4][..], &program.bytes[10..12]);
+    assert!(disassemble(&program.bytes).contains("  0010   in r4"));
+    let mut machine = Machine::new(&program.bytes);
+    let mut out = Vec::new();
+    machine.run_io(&mut &b"50 8\n!"[..], &mut out).unwrap();
+    assert_eq!(b"42!", &out[..]);
+}
@@ -0,0 +1,88 @@
+use interpreter::{Instruction, Machine, MachineError};
+
+#[test]
+fn decode_encode() {
+    let instructions = [
+        (Instruction::MoveIf(1, 2, 3), vec![1, 1, 2, 3]),
+        (Instruction::Store(2, 3), vec![2, 2, 3]),
+        (Instruction::Load(1, 2), vec![3, 1, 2]),
+        (Instruction::LoadImm(1, -2), vec![4, 1, 0xfe, 0xff]),
+        (Instruction::Sub(10, 2, 1), vec![5, 10, 2, 1]),
+        (Instruction::Out(5), vec![6, 5]),
+        (Instruction::Exit, vec![7]),
+        (Instruction::OutNumber(3), vec![8, 3]),
+        (Instruction::In(4), vec![9, 4]),
+        (Instruction::InNumber(4), vec![10, 4]),
+        (Instruction::Add(1, 2, 3), vec![11, 1, 2, 3]),
+        (Instruction::Not(1, 2), vec![20, 1, 2]),
+        (Instruction::SltU(1, 2, 3), vec![25, 1, 2, 3]),
+    ];
+    for (instruction, bytes) in instructions {
+        assert_eq!(bytes, instruction.encode());
+        assert_eq!(bytes.len(), instruction.size());
+        // Trailing bytes are ignored
+        let mut memory = bytes.clone();
+        memory.extend([7, 7, 7]);
+        assert_eq!(
+            (instruction, bytes.len()),
+            Instruction::decode(&memory).unwrap()
+        );
+    }
+}
+
+#[test]
+fn decode_errors() {
+    assert!(matches!(
+        Instruction::decode(&[]),
+        Err(MachineError::InvalidMemoryAccess {
+            address: 0,
+            opcode: None,
+            ..
+        })
+    ));
+    assert!(matches!(
+        Instruction::decode(&[0, 1, 2, 3]),
+        Err(MachineError::InvalidInstruction { ip: 0, opcode: 0 })
+    ));
+    assert!(matches!(
+        Instruction::decode(&[255, 1, 2, 3]),
+        Err(MachineError::InvalidInstruction { ip: 0, opcode: 255 })
+    ));
+    assert!(matches!(
+        Instruction::decode(&[4, 1, 0]),
+        Err(MachineError::InvalidMemoryAccess {
+            ip: 0,
+            opcode: Some(4),
+            address: 0,
+            width: 4,
+            host: false
+        })
+    ));
+}
+
+#[test]
+fn display() {
+    assert_eq!(
+        "move r0 <- r9 if r8 != 0",
+        Instruction::MoveIf(0, 9, 8).to_string()
+    );
+    assert_eq!("loadimm r3 <- #-4", Instruction::LoadImm(3, -4).to_string());
+    assert_eq!("store [r2] <- r3", Instruction::Store(2, 3).to_string());
+}
+
+#[test]
+fn execute() {
+    let mut machine = Machine::new(&[]);
+    let mut out = Vec::new();
+    assert!(!machine
+        .execute_on(Instruction::LoadImm(1, 42), &mut out)
+        .unwrap());
+    assert!(!machine
+        .execute_on(Instruction::OutNumber(1), &mut out)
+        .unwrap());
+    assert!(machine.execute_on(Instruction::Exit, &mut out).unwrap());
+    assert_eq!(&b"42"[..], &out[..]);
+    // The IP is left alone by execute
+    assert_eq!(0, machine.regs()[0]);
+    assert!(machine.execute(Instruction::Out(16)).is_err());
+}
@@ -0,0 +1,145 @@
+use interpreter::{
+    assemble, disassemble, Device, Machine, MachineError, Timer, IRQ_ENABLE, IRQ_IP, IRQ_PENDING,
+    IRQ_VECTOR, TIMER_BASE,
+};
+
+// Two tasks counting in r1 and r3, switched every time the timer expires.
+// The IP of the task which is not running is kept in r10.
+const PREEMPTIVE: &str = "\
+    loadimm r12 <- #1
+    loadimm r13 <- #-4072   ; timer status
+    loadimm r14 <- #-4076   ; timer limit
+    loadimm r15 <- #20
+    store [r14] <- r15
+    loadimm r10 <- #task_b
+    loadimm r14 <- #handler
+    wrctl c4 <- r14
+    wrctl c6 <- r12         ; enable line 0, the timer
+    store [r13] <- r12      ; start counting from now
+task_a:
+    add r1 <- r1, r12
+    jmp #task_a
+task_b:
+    add r3 <- r3, r12
+    jmp #task_b
+handler:
+    rdctl r11 <- c5
+    wrctl c5 <- r10
+    move r10 <- r11 if r12 != 0
+    store [r13] <- r12      ; acknowledge the timer
+    reti
+";
+
+// Two tasks printing their letter, giving the processor to each other by
+// calling yield. The return address of the task which is not running is
+// kept in r10.
+const COOPERATIVE: &str = "\
+    loadimm r2 <- #4096
+    loadimm r12 <- #1
+    loadimm r10 <- #task_b
+task_a:
+    loadimm r1 <- #65
+    out r1
+    call #yield
+    jmp #task_a
+task_b:
+    loadimm r1 <- #66
+    out r1
+    call #yield
+    loadimm r1 <- #98
+    out r1
+    call #yield
+    jmp #task_b
+yield:
+    pop r11
+    push r10
+    move r10 <- r11 if r12 != 0
+    ret
+";
+
+fn preemptive(steps: u64) -> Machine {
+    let program = assemble(PREEMPTIVE).unwrap();
+    let mut machine = Machine::new(&program.bytes);
+    machine.attach(TIMER_BASE, Box::new(Timer::new()));
+    assert!(matches!(
+        machine.run_on_with_limit(&mut Vec::new(), steps),
+        Err(MachineError::StepLimitExceeded { .. })
+    ));
+    machine
+}
+
+#[test]
+fn preemptive_scheduler() {
+    // After 10 steps of setup, task A runs for 19 steps, then the timer
+    // expires every 23 steps: 5 in the handler and 18 in the next task,
+    // whose loop counts once every 2 steps
+    let machine = preemptive(10 + 19);
+    assert_eq!([10, 0], [machine.regs()[1], machine.regs()[3]]);
+    let machine = preemptive(10 + 19 + 23);
+    assert_eq!([10, 9], [machine.regs()[1], machine.regs()[3]]);
+    let machine = preemptive(10 + 19 + 100 * 23);
+    assert_eq!([460, 450], [machine.regs()[1], machine.regs()[3]]);
+}
+
+#[test]
+fn cooperative_scheduler() {
+    let program = assemble(COOPERATIVE).unwrap();
+    let mut machine = Machine::new(&program.bytes);
+    let mut out = Vec::new();
+    assert!(machine.run_on_with_limit(&mut out, 100).is_err());
+    assert!(out.starts_with(b"ABAbABAb"));
+}
+
+#[test]
+fn masked_interrupt() {
+    // 0: load r1 <- [r2] with r2 pointing to the expired timer status,
+    // with the interrupt handler at 100
+    let mut machine = Machine::new(&[3, 1, 2, 3, 1, 2, 7]);
+    let mut timer = Timer::new();
+    timer.write32(4, 1).unwrap();
+    machine.attach(TIMER_BASE, Box::new(timer));
+    machine.set_reg(2, TIMER_BASE + 8).unwrap();
+    machine.set_control(IRQ_VECTOR, 100);
+    machine.step_on(&mut Vec::new()).unwrap();
+    assert_eq!(0, machine.regs()[1]);
+    // The line is pending but not enabled
+    machine.step_on(&mut Vec::new()).unwrap();
+    assert_eq!(1, machine.regs()[1]);
+    assert_eq!(6, machine.regs()[0]);
+    assert_eq!(1, machine.controls()[IRQ_PENDING as usize]);
+    machine.set_control(IRQ_ENABLE, 1);
+    assert!(matches!(
+        machine.step_on(&mut Vec::new()),
+        Err(MachineError::InvalidInstruction { ip: 100, opcode: 0 })
+    ));
+    assert_eq!(6, machine.controls()[IRQ_IP as usize]);
+}
+
+#[test]
+fn unacknowledged_interrupt() {
+    // 0: out r1, 2: exit, 3: handler: reti, with the timer expiring after
+    // the first step
+    let mut machine = Machine::new(&[6, 1, 7, 36]);
+    let mut timer = Timer::new();
+    timer.write32(4, 1).unwrap();
+    machine.attach(TIMER_BASE, Box::new(timer));
+    machine.set_control(IRQ_VECTOR, 3);
+    machine.set_control(IRQ_ENABLE, 1);
+    let mut out = Vec::new();
+    assert!(!machine.step_on(&mut out).unwrap());
+    // The handler returns without acknowledging the timer, which is still
+    // expired, so the exit is never reached
+    for _ in 0..3 {
+        assert!(!machine.step_on(&mut out).unwrap());
+        assert_eq!(2, machine.regs()[0]);
+        assert_eq!(2, machine.controls()[IRQ_IP as usize]);
+    }
+}
+
+#[test]
+fn interrupt_instructions() {
+    assert_eq!(vec![36], assemble("reti").unwrap().bytes);
+    let listing = disassemble(&assemble(PREEMPTIVE).unwrap().bytes);
+    assert!(listing.contains("loc_0050:\n  0050   rdctl r11 <- c5"));
+    assert!(listing.contains("  0063   reti"));
+}
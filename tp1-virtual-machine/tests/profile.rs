@@ -0,0 +1,65 @@
+use interpreter::{Machine, Profile, Symbols};
+
+fn profile(image: &[u8], n: u32) -> Profile {
+    let mut machine = Machine::new(image);
+    machine.set_reg(10, n).unwrap();
+    machine.set_profile(Some(Profile::new()));
+    machine.run_on(&mut Vec::new()).unwrap();
+    machine.profile().unwrap().clone()
+}
+
+#[test]
+fn fibo() {
+    let symbols = Symbols::from_listing(include_str!("fibo.dis")).unwrap();
+    let profile = profile(include_bytes!("fibo.bin"), 10);
+    assert_eq!(4723, profile.steps());
+    // fibo is called once for 10, and twice more by every call for 2 or more
+    assert_eq!(177, profile.hits(symbols.address("fibo").unwrap()));
+    assert_eq!(1, profile.hits(0));
+    assert_eq!(
+        profile.steps(),
+        profile.addresses().map(|(_, hits)| hits).sum()
+    );
+    assert_eq!(
+        profile.steps(),
+        (0..=255).map(|op| profile.opcode_hits(op)).sum()
+    );
+    // Every word pushed on the stack is read back
+    assert_eq!(353, profile.reads());
+    assert_eq!(353, profile.writes());
+    assert_eq!(
+        ("return_from_fibo_2".to_string(), 1320),
+        profile.hottest(&symbols)[0]
+    );
+}
+
+#[test]
+fn annotated_listing() {
+    let listing = include_str!("../examples/99bottles.dis");
+    let symbols = Symbols::from_listing(listing).unwrap();
+    let profile = profile(include_bytes!("../examples/99bottles.bin"), 0);
+    let annotated = profile.annotate(listing);
+    assert_eq!(listing.lines().count(), annotated.lines().count());
+    let lines: Vec<&str> = annotated.lines().collect();
+    assert_eq!("         1    0000   loadimm r2 <- #4096", lines[0]);
+    assert_eq!("            loop:", lines[2]);
+    assert_eq!("       100    0008   loadimm r3 <- #4", lines[3]);
+
+    let summary = profile.summary(&symbols, 2);
+    assert!(summary.starts_with("128504 instructions executed\n"));
+    assert!(summary.contains("     58194  45.3%  loadimm\n"));
+    assert!(summary
+        .ends_with("hottest labels:\n     78624  61.2%  ite_then_6\n     24558  19.1%  print\n"));
+}
+
+#[test]
+fn accumulated_runs() {
+    let image = include_bytes!("fibo.bin");
+    let mut machine = Machine::new(image);
+    machine.set_profile(Some(profile(image, 10)));
+    machine.set_reg(10, 10).unwrap();
+    machine.run_on(&mut Vec::new()).unwrap();
+    assert_eq!(2 * 4723, machine.profile().unwrap().steps());
+    machine.set_profile(None);
+    assert!(machine.profile().is_none());
+}
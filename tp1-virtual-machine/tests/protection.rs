@@ -0,0 +1,105 @@
+use interpreter::{
+    Machine, MachineError, Permissions, Region, CAUSE_PROTECTION, TRAP_CAUSE, TRAP_VALUE,
+    TRAP_VECTOR,
+};
+
+// Enough recursive calls for the stack to reach the code.
+const DEEP: u32 = 2000;
+
+#[test]
+fn runaway_stack() {
+    let image = include_bytes!("rfact.bin");
+    let mut machine = Machine::new(image);
+    machine.protect_image();
+    machine.set_reg(10, DEEP).unwrap();
+    let error = machine.run_on(&mut Vec::new()).unwrap_err();
+    assert!(matches!(
+        &error,
+        MachineError::WriteProtected { address: 204, region, .. } if region == "text"
+    ));
+    assert_eq!(
+        "write at address 0xcc denied by region text at 0142",
+        error.to_string()
+    );
+    // The code is intact
+    assert_eq!(&image[..], &machine.memory()[..image.len()]);
+}
+
+#[test]
+fn stack_region() {
+    let mut machine = Machine::new(include_bytes!("rfact_native.bin"));
+    machine.protect_image();
+    machine.protect_stack(256);
+    assert_eq!(
+        ["0000-0041 r-x text", "3840-4096 rw- stack"],
+        machine
+            .regions()
+            .iter()
+            .map(|r| r.to_string())
+            .collect::<Vec<_>>()[..]
+    );
+    machine.set_reg(10, DEEP).unwrap();
+    assert!(matches!(
+        machine.run_on(&mut Vec::new()),
+        Err(MachineError::StackOverflow { sp: 3840, .. })
+    ));
+
+    // Code cannot run from the stack
+    machine.set_reg(0, 4000).unwrap();
+    let error = machine.step_on(&mut Vec::new()).unwrap_err();
+    assert!(matches!(
+        &error,
+        MachineError::ExecuteProtected { ip: 4000, region } if region == "stack"
+    ));
+    assert_eq!(
+        "execution denied by region stack at 4000",
+        error.to_string()
+    );
+}
+
+#[test]
+#[should_panic(expected = "overlaps the program image")]
+fn stack_over_image() {
+    Machine::new(&[7; 100]).protect_stack(4000);
+}
+
+#[test]
+fn trapped_violation() {
+    // 0: load r1 <- [r2] with r2 == 2000, in a region which cannot be read
+    let mut machine = Machine::new(&[3, 1, 2]);
+    let hidden = Permissions {
+        read: false,
+        ..Permissions::RW
+    };
+    machine.protect(Region::new("secret", 1000..3000, hidden));
+    machine.set_reg(2, 2000).unwrap();
+    let error = machine.step_on(&mut Vec::new()).unwrap_err();
+    assert_eq!(
+        "read at address 0x7d0 denied by region secret at 0000",
+        error.to_string()
+    );
+
+    machine.set_reg(0, 0).unwrap();
+    machine.set_control(TRAP_VECTOR, 100);
+    assert!(!machine.step_on(&mut Vec::new()).unwrap());
+    assert_eq!(100, machine.regs()[0]);
+    assert_eq!(CAUSE_PROTECTION, machine.controls()[TRAP_CAUSE as usize]);
+    assert_eq!(2000, machine.controls()[TRAP_VALUE as usize]);
+}
+
+#[test]
+fn unprotected_accesses() {
+    // Host writes and accesses outside regions are not restricted
+    let mut machine = Machine::new(&[2, 2, 1, 7]);
+    machine.protect_image();
+    machine.set_memory(3, &[7]).unwrap();
+    machine.set_reg(2, 4).unwrap();
+    machine.run_on(&mut Vec::new()).unwrap();
+    // A word straddling the end of the image is refused
+    machine.set_reg(0, 0).unwrap();
+    machine.set_reg(2, 1).unwrap();
+    assert!(matches!(
+        machine.run_on(&mut Vec::new()),
+        Err(MachineError::WriteProtected { address: 1, .. })
+    ));
+}
@@ -0,0 +1,127 @@
+use interpreter::{
+    Framebuffer, Machine, MachineConfig, MachineError, Snapshot, Timer, FRAMEBUFFER_BASE,
+    FRAME_SIZE, SNAPSHOT_VERSION, TIMER_BASE,
+};
+use std::io;
+
+mod common;
+use common::Shared;
+
+#[test]
+fn fork() {
+    let mut machine = Machine::new(include_bytes!("../examples/factorial.bin"));
+    let mut start = Vec::new();
+    assert!(machine.run_on_with_limit(&mut start, 100).is_err());
+    let snapshot = machine.snapshot();
+    let mut end = Vec::new();
+    machine.run_on(&mut end).unwrap();
+
+    // A new machine picks up where the first one was
+    let mut fork = Machine::new(&[]);
+    fork.restore(&snapshot).unwrap();
+    assert_eq!(snapshot, fork.snapshot());
+    let mut fork_end = Vec::new();
+    fork.run_on(&mut fork_end).unwrap();
+    assert_eq!(end, fork_end);
+    assert_eq!(machine.regs(), fork.regs());
+    assert_eq!(machine.memory(), fork.memory());
+}
+
+#[test]
+fn protected_regions() {
+    let mut machine = Machine::new(include_bytes!("rfact.bin"));
+    machine.protect_image();
+    let bytes = machine.snapshot().to_bytes().unwrap();
+
+    // The resumed program still cannot overwrite its code
+    let mut resumed = Machine::new(&[]);
+    resumed
+        .restore(&Snapshot::from_bytes(&bytes).unwrap())
+        .unwrap();
+    assert_eq!(machine.regions(), resumed.regions());
+    resumed.set_reg(10, 2000).unwrap();
+    assert!(matches!(
+        resumed.run_on(&mut Vec::new()),
+        Err(MachineError::WriteProtected { address: 204, .. })
+    ));
+    // and knows the size of its image
+    resumed.protect_image();
+    assert_eq!(machine.regions()[0], resumed.regions()[1]);
+}
+
+fn with_devices(frames: &Shared) -> Machine {
+    let mut machine = Machine::new(include_bytes!("../examples/diagonal.bin"));
+    machine.attach(TIMER_BASE, Box::new(Timer::new()));
+    machine.attach(FRAMEBUFFER_BASE, Box::new(Framebuffer::new(frames.clone())));
+    machine
+}
+
+#[test]
+fn device_state() {
+    let frames = Shared::default();
+    let mut machine = with_devices(&frames);
+    while frames.0.borrow().len() < 4 * (FRAME_SIZE + 1) {
+        machine.step_on(&mut Vec::new()).unwrap();
+    }
+    let bytes = machine.snapshot().to_bytes().unwrap();
+    machine.run_on(&mut Vec::new()).unwrap();
+
+    // The resumed framebuffer only sends the remaining images
+    let resumed_frames = Shared::default();
+    let mut resumed = with_devices(&resumed_frames);
+    resumed
+        .restore(&Snapshot::from_bytes(&bytes).unwrap())
+        .unwrap();
+    resumed.run_on(&mut Vec::new()).unwrap();
+    assert_eq!(
+        &frames.0.borrow()[4 * (FRAME_SIZE + 1)..],
+        &resumed_frames.0.borrow()[..]
+    );
+    assert_eq!(machine.snapshot(), resumed.snapshot());
+}
+
+#[test]
+fn mismatched_machines() {
+    let snapshot = with_devices(&Shared::default()).snapshot();
+    let error = Machine::new(&[]).restore(&snapshot).unwrap_err();
+    assert_eq!(
+        "invalid snapshot: 2 device(s) saved, 0 attached",
+        error.to_string()
+    );
+    let config = MachineConfig {
+        memory_size: 8192,
+        ..MachineConfig::default()
+    };
+    let mut larger = Machine::with_config(&[], config).unwrap();
+    assert_eq!(
+        "invalid snapshot: taken from a machine with 4096 bytes of memory and 16 registers",
+        larger.restore(&snapshot).unwrap_err().to_string()
+    );
+    // Devices are told apart by their state
+    let mut swapped = Machine::new(&[]);
+    swapped.attach(TIMER_BASE, Box::new(Framebuffer::new(io::sink())));
+    swapped.attach(FRAMEBUFFER_BASE, Box::new(Timer::new()));
+    assert!(matches!(
+        swapped.restore(&snapshot),
+        Err(MachineError::InvalidSnapshot { .. })
+    ));
+}
+
+#[test]
+fn invalid_snapshots() {
+    let bytes = Machine::new(&[7]).snapshot().to_bytes().unwrap();
+    assert_eq!(b"SE202VM\0", &bytes[..8]);
+    assert_eq!(SNAPSHOT_VERSION.to_le_bytes(), bytes[8..12]);
+    let reason = |bytes: &[u8]| Snapshot::from_bytes(bytes).unwrap_err().to_string();
+    assert_eq!("invalid snapshot: not a snapshot", reason(&[7]));
+    let mut future = bytes.clone();
+    future[8] = 3;
+    assert_eq!("invalid snapshot: unsupported version 3", reason(&future));
+    assert_eq!(
+        "invalid snapshot: truncated data",
+        reason(&bytes[..bytes.len() - 1])
+    );
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!("invalid snapshot: trailing data", reason(&trailing));
+}
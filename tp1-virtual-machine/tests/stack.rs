@@ -0,0 +1,91 @@
+use interpreter::{assemble, Instruction, Machine, MachineError};
+
+fn fact(n: u32) -> u32 {
+    (1..=n).product()
+}
+
+#[test]
+fn push_pop() {
+    let mut machine = Machine::new(&[]);
+    let mut out = Vec::new();
+    machine.set_reg(2, 4096).unwrap();
+    machine.set_reg(3, 42).unwrap();
+    machine.execute_on(Instruction::Push(3), &mut out).unwrap();
+    assert_eq!(4092, machine.regs()[2]);
+    assert_eq!(&[42, 0, 0, 0], &machine.memory()[4092..]);
+    machine.execute_on(Instruction::Pop(4), &mut out).unwrap();
+    assert_eq!(4096, machine.regs()[2]);
+    assert_eq!(42, machine.regs()[4]);
+    // Popping into r2 keeps the popped value
+    machine.execute_on(Instruction::Push(3), &mut out).unwrap();
+    machine.execute_on(Instruction::Pop(2), &mut out).unwrap();
+    assert_eq!(42, machine.regs()[2]);
+}
+
+#[test]
+fn call_ret() {
+    // 0: call #6, 3: exit, 4: [0, 0], 6: ret
+    let mut machine = Machine::new(&[28, 6, 0, 7, 0, 0, 29]);
+    machine.set_reg(2, 4096).unwrap();
+    assert!(!machine.step_on(&mut Vec::new()).unwrap());
+    assert_eq!(6, machine.regs()[0]);
+    assert_eq!(4092, machine.regs()[2]);
+    assert_eq!(&[3, 0, 0, 0], &machine.memory()[4092..]);
+    assert!(!machine.step_on(&mut Vec::new()).unwrap());
+    assert_eq!(3, machine.regs()[0]);
+    assert_eq!(4096, machine.regs()[2]);
+    assert!(machine.step_on(&mut Vec::new()).unwrap());
+}
+
+#[test]
+fn stack_errors() {
+    // The stack cannot grow into the program: 0: push r1, 2: pop r1
+    let mut machine = Machine::new(&[26, 1, 27, 1]);
+    machine.set_reg(2, 6).unwrap();
+    let error = machine.step_on(&mut Vec::new()).unwrap_err();
+    assert!(matches!(
+        error,
+        MachineError::StackOverflow { ip: 0, sp: 6 }
+    ));
+    assert_eq!("stack overflow at 0000, with r2 at 6", error.to_string());
+    assert_eq!(6, machine.regs()[2]);
+
+    // The push was skipped, and the stack is empty
+    machine.set_reg(2, 4096).unwrap();
+    let error = machine.step_on(&mut Vec::new()).unwrap_err();
+    assert!(matches!(
+        error,
+        MachineError::StackUnderflow { ip: 2, sp: 4096 }
+    ));
+    assert_eq!(4096, machine.regs()[2]);
+
+    // A return from an empty stack is an underflow as well
+    let mut machine = Machine::new(&[29]);
+    machine.set_reg(2, 4096).unwrap();
+    assert!(matches!(
+        machine.step_on(&mut Vec::new()),
+        Err(MachineError::StackUnderflow { ip: 0, sp: 4096 })
+    ));
+}
+
+#[test]
+fn assemble_calls() {
+    let program = assemble("call #f\nexit\nf:\npush r3\npop r3\nret\n").unwrap();
+    assert_eq!(vec![28, 4, 0, 7, 26, 3, 27, 3, 29], program.bytes);
+    assert!(assemble("call r3").is_err());
+}
+
+// Recursive factorial using the native stack instructions
+#[test]
+fn rfact_native() {
+    let program = assemble(include_str!("rfact_native.dis")).unwrap();
+    assert_eq!(&include_bytes!("rfact_native.bin")[..], &program.bytes[..]);
+    assert!(program.bytes.len() < include_bytes!("rfact.bin").len());
+    for i in 1..13 {
+        let mut machine = Machine::new(&program.bytes);
+        machine.set_reg(10, i).unwrap();
+        machine.run_on(&mut Vec::new()).unwrap();
+        assert_eq!(fact(i), machine.regs()[11]);
+        assert_eq!(4096, machine.regs()[2]);
+    }
+}
@@ -0,0 +1,61 @@
+use interpreter::{Machine, Symbols, Tracer};
+
+mod common;
+use common::Shared;
+
+fn squeeze(line: &str) -> String {
+    line.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[test]
+fn symbols() {
+    let symbols = Symbols::from_listing(include_str!("rfact.dis")).unwrap();
+    assert_eq!(Some(87), symbols.address("rfact"));
+    assert_eq!("rfact", symbols.name(87));
+    assert_eq!("rfact+12", symbols.name(99));
+    // The first label defined at an address is used
+    assert_eq!("ite_end_2+4", symbols.name(191));
+    assert_eq!(None, symbols.lookup(10));
+    assert_eq!("0010", symbols.name(10));
+}
+
+#[test]
+fn trace_function() {
+    let out = Shared::default();
+    let symbols = Symbols::from_listing(include_str!("function.dis")).unwrap();
+    let mut machine = Machine::new(include_bytes!("function.bin"));
+    machine.set_tracer(Some(Tracer::new(out.clone()).with_symbols(symbols)));
+    machine.run().unwrap();
+    let lines: Vec<String> = out.lines().iter().map(|l| squeeze(l)).collect();
+    assert_eq!(13, lines.len());
+    assert_eq!("0000 loadimm r2 <- #4096 r2=4096", lines[0]);
+    assert_eq!("0016 store [r2] <- r3 [4092]=23", lines[4]);
+    assert_eq!("0019 loadimm r0 <- #24 -> myfunc", lines[5]);
+    assert_eq!("0028 myfunc+4 loadimm r3 <- #-4 r3=-4", lines[7]);
+    assert_eq!(
+        "0044 myfunc+20 load r0 <- [r3] -> return_from_myfunc_1",
+        lines[11]
+    );
+    assert_eq!("0023 return_from_myfunc_1 exit", lines[12]);
+}
+
+#[test]
+fn trace_error() {
+    // 0: load r1 <- [r1] with r1 == 30000
+    let out = Shared::default();
+    let mut machine = Machine::new(&[3, 1, 1]);
+    machine.set_reg(1, 30000).unwrap();
+    machine.set_tracer(Some(Tracer::new(out.clone())));
+    assert!(machine.step().is_err());
+    assert!(machine.step().is_err());
+    let lines = out.lines();
+    assert_eq!(2, lines.len());
+    assert!(lines[0].starts_with("0000 load r1 <- [r1]"));
+    assert!(lines[0].contains("!! invalid memory access of 4 byte(s) at address 0x7530"));
+    assert!(lines[1].starts_with("0003 ????"));
+
+    // Tracing can be stopped
+    machine.set_tracer(None);
+    assert!(machine.step().is_err());
+    assert_eq!(2, out.lines().len());
+}
@@ -0,0 +1,217 @@
+// Generated by vmaot, do not edit.
+#![allow(clippy::all, unused)]
+
+use interpreter::{MachineError, Memory, Translated};
+use std::io::Write;
+
+/// Image the module was translated from.
+pub const IMAGE: &[u8] = &[
+    4, 2, 0, 16, 4, 3, 4, 0, 5, 2, 2, 3, 4, 3, 23, 0, 2, 2, 3, 4, 0, 24, 0, 7, 4, 8, 59, 0, 1, 0,
+    8, 10, 4, 11, 0, 0, 4, 3, 252, 255, 5, 2, 2, 3, 4, 3, 4, 0, 5, 3, 2, 3, 3, 0, 3, 4, 0, 59, 0,
+    4, 8, 1, 0, 5, 8, 10, 8, 4, 9, 102, 0, 1, 0, 9, 8, 4, 11, 1, 0, 4, 3, 252, 255, 5, 2, 2, 3, 4,
+    3, 4, 0, 5, 3, 2, 3, 3, 0, 3, 4, 0, 224, 0, 4, 3, 1, 0, 5, 10, 10, 3, 4, 3, 4, 0, 5, 2, 2, 3,
+    2, 2, 10, 4, 3, 4, 0, 5, 2, 2, 3, 4, 3, 140, 0, 2, 2, 3, 4, 0, 24, 0, 4, 3, 252, 255, 5, 2, 2,
+    3, 4, 3, 4, 0, 5, 3, 2, 3, 3, 10, 3, 4, 3, 4, 0, 5, 2, 2, 3, 2, 2, 11, 4, 3, 1, 0, 5, 10, 10,
+    3, 4, 3, 4, 0, 5, 2, 2, 3, 4, 3, 197, 0, 2, 2, 3, 4, 0, 24, 0, 4, 3, 252, 255, 5, 2, 2, 3, 4,
+    3, 4, 0, 5, 3, 2, 3, 3, 10, 3, 5, 11, 1, 11, 5, 11, 10, 11, 4, 3, 252, 255, 5, 2, 2, 3, 4, 3,
+    4, 0, 5, 3, 2, 3, 3, 0, 3,
+];
+
+/// Program ready to run from its first instruction.
+pub fn new() -> Translated {
+    Translated::new(IMAGE, run)
+}
+
+fn run(r: &mut [u32; 16], m: &mut Memory, output: &mut dyn Write) -> Result<bool, MachineError> {
+    loop {
+        r[0] = match r[0] {
+            0 => {
+                // 0000   loadimm r2 <- #4096
+                r[2] = 4096;
+                // 0004   loadimm r3 <- #4
+                r[3] = 4;
+                // 0008   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0012   loadimm r3 <- #23
+                r[3] = 23;
+                // 0016   store [r2] <- r3
+                if !m.store(r[2], r[3]) {
+                    r[0] = 16;
+                    return Ok(false);
+                }
+                // 0019   loadimm r0 <- #24
+                24
+            }
+            23 => {
+                // 0023   exit
+                r[0] = 24;
+                return Ok(true);
+            }
+            24 => {
+                // 0024   loadimm r8 <- #59
+                r[8] = 59;
+                // 0028   move r0 <- r8 if r10 != 0
+                if r[10] != 0 {
+                    r[8]
+                } else {
+                    32
+                }
+            }
+            32 => {
+                // 0032   loadimm r11 <- #0
+                r[11] = 0;
+                // 0036   loadimm r3 <- #-4
+                r[3] = 4294967292;
+                // 0040   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0044   loadimm r3 <- #4
+                r[3] = 4;
+                // 0048   sub r3 <- r2 - r3
+                r[3] = r[2].wrapping_sub(r[3]);
+                // 0052   load r0 <- [r3]
+                let Some(value) = m.load(r[3]) else {
+                    r[0] = 52;
+                    return Ok(false);
+                };
+                value
+            }
+            59 => {
+                // 0059   loadimm r8 <- #1
+                r[8] = 1;
+                // 0063   sub r8 <- r10 - r8
+                r[8] = r[10].wrapping_sub(r[8]);
+                // 0067   loadimm r9 <- #102
+                r[9] = 102;
+                // 0071   move r0 <- r9 if r8 != 0
+                if r[8] != 0 {
+                    r[9]
+                } else {
+                    75
+                }
+            }
+            75 => {
+                // 0075   loadimm r11 <- #1
+                r[11] = 1;
+                // 0079   loadimm r3 <- #-4
+                r[3] = 4294967292;
+                // 0083   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0087   loadimm r3 <- #4
+                r[3] = 4;
+                // 0091   sub r3 <- r2 - r3
+                r[3] = r[2].wrapping_sub(r[3]);
+                // 0095   load r0 <- [r3]
+                let Some(value) = m.load(r[3]) else {
+                    r[0] = 95;
+                    return Ok(false);
+                };
+                value
+            }
+            102 => {
+                // 0102   loadimm r3 <- #1
+                r[3] = 1;
+                // 0106   sub r10 <- r10 - r3
+                r[10] = r[10].wrapping_sub(r[3]);
+                // 0110   loadimm r3 <- #4
+                r[3] = 4;
+                // 0114   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0118   store [r2] <- r10
+                if !m.store(r[2], r[10]) {
+                    r[0] = 118;
+                    return Ok(false);
+                }
+                // 0121   loadimm r3 <- #4
+                r[3] = 4;
+                // 0125   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0129   loadimm r3 <- #140
+                r[3] = 140;
+                // 0133   store [r2] <- r3
+                if !m.store(r[2], r[3]) {
+                    r[0] = 133;
+                    return Ok(false);
+                }
+                // 0136   loadimm r0 <- #24
+                24
+            }
+            140 => {
+                // 0140   loadimm r3 <- #-4
+                r[3] = 4294967292;
+                // 0144   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0148   loadimm r3 <- #4
+                r[3] = 4;
+                // 0152   sub r3 <- r2 - r3
+                r[3] = r[2].wrapping_sub(r[3]);
+                // 0156   load r10 <- [r3]
+                let Some(value) = m.load(r[3]) else {
+                    r[0] = 156;
+                    return Ok(false);
+                };
+                r[10] = value;
+                // 0159   loadimm r3 <- #4
+                r[3] = 4;
+                // 0163   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0167   store [r2] <- r11
+                if !m.store(r[2], r[11]) {
+                    r[0] = 167;
+                    return Ok(false);
+                }
+                // 0170   loadimm r3 <- #1
+                r[3] = 1;
+                // 0174   sub r10 <- r10 - r3
+                r[10] = r[10].wrapping_sub(r[3]);
+                // 0178   loadimm r3 <- #4
+                r[3] = 4;
+                // 0182   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0186   loadimm r3 <- #197
+                r[3] = 197;
+                // 0190   store [r2] <- r3
+                if !m.store(r[2], r[3]) {
+                    r[0] = 190;
+                    return Ok(false);
+                }
+                // 0193   loadimm r0 <- #24
+                24
+            }
+            197 => {
+                // 0197   loadimm r3 <- #-4
+                r[3] = 4294967292;
+                // 0201   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0205   loadimm r3 <- #4
+                r[3] = 4;
+                // 0209   sub r3 <- r2 - r3
+                r[3] = r[2].wrapping_sub(r[3]);
+                // 0213   load r10 <- [r3]
+                let Some(value) = m.load(r[3]) else {
+                    r[0] = 213;
+                    return Ok(false);
+                };
+                r[10] = value;
+                // 0216   sub r11 <- r1 - r11
+                r[11] = r[1].wrapping_sub(r[11]);
+                // 0220   sub r11 <- r10 - r11
+                r[11] = r[10].wrapping_sub(r[11]);
+                // 0224   loadimm r3 <- #-4
+                r[3] = 4294967292;
+                // 0228   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0232   loadimm r3 <- #4
+                r[3] = 4;
+                // 0236   sub r3 <- r2 - r3
+                r[3] = r[2].wrapping_sub(r[3]);
+                // 0240   load r0 <- [r3]
+                let Some(value) = m.load(r[3]) else {
+                    r[0] = 240;
+                    return Ok(false);
+                };
+                value
+            }
+            _ => return Ok(false),
+        };
+    }
+}
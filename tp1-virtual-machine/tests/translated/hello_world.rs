@@ -0,0 +1,155 @@
+// Generated by vmaot, do not edit.
+#![allow(clippy::all, unused)]
+
+use interpreter::{MachineError, Memory, Translated};
+use std::io::Write;
+
+/// Image the module was translated from.
+pub const IMAGE: &[u8] = &[
+    4, 2, 0, 16, 4, 3, 4, 0, 5, 2, 2, 3, 2, 2, 10, 4, 3, 4, 0, 5, 2, 2, 3, 2, 2, 11, 4, 10, 148, 0,
+    4, 11, 14, 0, 4, 3, 4, 0, 5, 2, 2, 3, 4, 3, 53, 0, 2, 2, 3, 4, 0, 92, 0, 4, 3, 252, 255, 5, 2,
+    2, 3, 4, 3, 4, 0, 5, 3, 2, 3, 3, 11, 3, 4, 3, 252, 255, 5, 2, 2, 3, 4, 3, 4, 0, 5, 3, 2, 3, 3,
+    10, 3, 7, 4, 8, 104, 0, 1, 0, 8, 11, 4, 0, 129, 0, 3, 3, 10, 6, 3, 4, 3, 255, 255, 5, 10, 10,
+    3, 4, 3, 1, 0, 5, 11, 11, 3, 4, 0, 92, 0, 4, 3, 252, 255, 5, 2, 2, 3, 4, 3, 4, 0, 5, 3, 2, 3,
+    3, 0, 3, 72, 101, 108, 108, 111, 44, 32, 119, 111, 114, 108, 100, 33, 10,
+];
+
+/// Program ready to run from its first instruction.
+pub fn new() -> Translated {
+    Translated::new(IMAGE, run)
+}
+
+fn run(r: &mut [u32; 16], m: &mut Memory, output: &mut dyn Write) -> Result<bool, MachineError> {
+    loop {
+        r[0] = match r[0] {
+            0 => {
+                // 0000   loadimm r2 <- #4096
+                r[2] = 4096;
+                // 0004   loadimm r3 <- #4
+                r[3] = 4;
+                // 0008   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0012   store [r2] <- r10
+                if !m.store(r[2], r[10]) {
+                    r[0] = 12;
+                    return Ok(false);
+                }
+                // 0015   loadimm r3 <- #4
+                r[3] = 4;
+                // 0019   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0023   store [r2] <- r11
+                if !m.store(r[2], r[11]) {
+                    r[0] = 23;
+                    return Ok(false);
+                }
+                // 0026   loadimm r10 <- #148
+                r[10] = 148;
+                // 0030   loadimm r11 <- #14
+                r[11] = 14;
+                // 0034   loadimm r3 <- #4
+                r[3] = 4;
+                // 0038   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0042   loadimm r3 <- #53
+                r[3] = 53;
+                // 0046   store [r2] <- r3
+                if !m.store(r[2], r[3]) {
+                    r[0] = 46;
+                    return Ok(false);
+                }
+                // 0049   loadimm r0 <- #92
+                92
+            }
+            53 => {
+                // 0053   loadimm r3 <- #-4
+                r[3] = 4294967292;
+                // 0057   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0061   loadimm r3 <- #4
+                r[3] = 4;
+                // 0065   sub r3 <- r2 - r3
+                r[3] = r[2].wrapping_sub(r[3]);
+                // 0069   load r11 <- [r3]
+                let Some(value) = m.load(r[3]) else {
+                    r[0] = 69;
+                    return Ok(false);
+                };
+                r[11] = value;
+                // 0072   loadimm r3 <- #-4
+                r[3] = 4294967292;
+                // 0076   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0080   loadimm r3 <- #4
+                r[3] = 4;
+                // 0084   sub r3 <- r2 - r3
+                r[3] = r[2].wrapping_sub(r[3]);
+                // 0088   load r10 <- [r3]
+                let Some(value) = m.load(r[3]) else {
+                    r[0] = 88;
+                    return Ok(false);
+                };
+                r[10] = value;
+                // 0091   exit
+                r[0] = 92;
+                return Ok(true);
+            }
+            92 => {
+                // 0092   loadimm r8 <- #104
+                r[8] = 104;
+                // 0096   move r0 <- r8 if r11 != 0
+                if r[11] != 0 {
+                    r[8]
+                } else {
+                    100
+                }
+            }
+            100 => {
+                // 0100   loadimm r0 <- #129
+                129
+            }
+            104 => {
+                // 0104   load r3 <- [r10]
+                let Some(value) = m.load(r[10]) else {
+                    r[0] = 104;
+                    return Ok(false);
+                };
+                r[3] = value;
+                // 0107   out r3
+                r[0] = 109;
+                write!(output, "{}", r[3] as u8 as char)?;
+                // 0109   loadimm r3 <- #-1
+                r[3] = 4294967295;
+                // 0113   sub r10 <- r10 - r3
+                r[10] = r[10].wrapping_sub(r[3]);
+                // 0117   loadimm r3 <- #1
+                r[3] = 1;
+                // 0121   sub r11 <- r11 - r3
+                r[11] = r[11].wrapping_sub(r[3]);
+                // 0125   loadimm r0 <- #92
+                92
+            }
+            129 => {
+                // 0129   loadimm r3 <- #-4
+                r[3] = 4294967292;
+                // 0133   sub r2 <- r2 - r3
+                r[2] = r[2].wrapping_sub(r[3]);
+                // 0137   loadimm r3 <- #4
+                r[3] = 4;
+                // 0141   sub r3 <- r2 - r3
+                r[3] = r[2].wrapping_sub(r[3]);
+                // 0145   load r0 <- [r3]
+                let Some(value) = m.load(r[3]) else {
+                    r[0] = 145;
+                    return Ok(false);
+                };
+                value
+            }
+            148 => {
+                r[0] = 148;
+                return Ok(false);
+            }
+            _ => return Ok(false),
+        };
+    }
+}
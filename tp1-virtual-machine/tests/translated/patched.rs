@@ -0,0 +1,66 @@
+// Generated by vmaot, do not edit.
+#![allow(clippy::all, unused)]
+
+use interpreter::{MachineError, Memory, Translated};
+use std::io::Write;
+
+/// Image the module was translated from.
+pub const IMAGE: &[u8] = &[
+    4, 3, 1, 0, 4, 4, 28, 0, 4, 5, 7, 0, 4, 6, 16, 0, 21, 5, 5, 6, 4, 6, 4, 6, 18, 5, 5, 6, 4, 6,
+    0, 0, 8, 6, 2, 4, 5, 5, 1, 1, 3, 32, 1, 239, 255, 7,
+];
+
+/// Program ready to run from its first instruction.
+pub fn new() -> Translated {
+    Translated::new(IMAGE, run)
+}
+
+fn run(r: &mut [u32; 16], m: &mut Memory, output: &mut dyn Write) -> Result<bool, MachineError> {
+    loop {
+        r[0] = match r[0] {
+            0 => {
+                // 0000   loadimm r3 <- #1
+                r[3] = 1;
+                // 0004   loadimm r4 <- #28
+                r[4] = 28;
+                // 0008   loadimm r5 <- #7
+                r[5] = 7;
+                // 0012   loadimm r6 <- #16
+                r[6] = 16;
+                // 0016   shl r5 <- r5, r6
+                r[5] = r[5].wrapping_shl(r[6]);
+                // 0020   loadimm r6 <- #1540
+                r[6] = 1540;
+                // 0024   or r5 <- r5, r6
+                r[5] = r[5] | r[6];
+                28
+            }
+            28 => {
+                // 0028   loadimm r6 <- #0
+                r[6] = 0;
+                // 0032   out_number r6
+                r[0] = 34;
+                write!(output, "{}", r[6] as i32)?;
+                // 0034   store [r4] <- r5
+                if !m.store(r[4], r[5]) {
+                    r[0] = 34;
+                    return Ok(false);
+                }
+                // 0037   sub r1 <- r1 - r3
+                r[1] = r[1].wrapping_sub(r[3]);
+                // 0041   bnz r1, #-17
+                if r[1] != 0 {
+                    28
+                } else {
+                    45
+                }
+            }
+            45 => {
+                // 0045   exit
+                r[0] = 46;
+                return Ok(true);
+            }
+            _ => return Ok(false),
+        };
+    }
+}
@@ -0,0 +1,98 @@
+// Generated by vmaot, do not edit.
+#![allow(clippy::all, unused)]
+
+use interpreter::{MachineError, Memory, Translated};
+use std::io::Write;
+
+/// Image the module was translated from.
+pub const IMAGE: &[u8] = &[
+    4, 2, 0, 16, 28, 8, 0, 7, 4, 11, 1, 0, 4, 9, 21, 0, 1, 0, 9, 10, 29, 26, 10, 4, 3, 1, 0, 5, 10,
+    10, 3, 28, 8, 0, 27, 10, 12, 11, 11, 10, 29,
+];
+
+/// Program ready to run from its first instruction.
+pub fn new() -> Translated {
+    Translated::new(IMAGE, run)
+}
+
+fn run(r: &mut [u32; 16], m: &mut Memory, output: &mut dyn Write) -> Result<bool, MachineError> {
+    loop {
+        r[0] = match r[0] {
+            0 => {
+                // 0000   loadimm r2 <- #4096
+                r[2] = 4096;
+                // 0004   call #8
+                let Some(sp) = m.push(r[2], 7) else {
+                    r[0] = 4;
+                    return Ok(false);
+                };
+                r[2] = sp;
+                8
+            }
+            7 => {
+                // 0007   exit
+                r[0] = 8;
+                return Ok(true);
+            }
+            8 => {
+                // 0008   loadimm r11 <- #1
+                r[11] = 1;
+                // 0012   loadimm r9 <- #21
+                r[9] = 21;
+                // 0016   move r0 <- r9 if r10 != 0
+                if r[10] != 0 {
+                    r[9]
+                } else {
+                    20
+                }
+            }
+            20 => {
+                // 0020   ret
+                let Some((sp, value)) = m.pop(r[2]) else {
+                    r[0] = 20;
+                    return Ok(false);
+                };
+                r[2] = sp;
+                value
+            }
+            21 => {
+                // 0021   push r10
+                let Some(sp) = m.push(r[2], r[10]) else {
+                    r[0] = 21;
+                    return Ok(false);
+                };
+                r[2] = sp;
+                // 0023   loadimm r3 <- #1
+                r[3] = 1;
+                // 0027   sub r10 <- r10 - r3
+                r[10] = r[10].wrapping_sub(r[3]);
+                // 0031   call #8
+                let Some(sp) = m.push(r[2], 34) else {
+                    r[0] = 31;
+                    return Ok(false);
+                };
+                r[2] = sp;
+                8
+            }
+            34 => {
+                // 0034   pop r10
+                let Some((sp, value)) = m.pop(r[2]) else {
+                    r[0] = 34;
+                    return Ok(false);
+                };
+                r[2] = sp;
+                r[10] = value;
+                // 0036   mul r11 <- r11, r10
+                r[11] = r[11].wrapping_mul(r[10]);
+                // 0040   ret
+                let Some((sp, value)) = m.pop(r[2]) else {
+                    r[0] = 40;
+                    return Ok(false);
+                };
+                r[2] = sp;
+                value
+            }
+            _ => return Ok(false),
+        };
+    }
+}
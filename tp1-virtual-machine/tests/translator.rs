@@ -0,0 +1,103 @@
+use interpreter::{translate, Machine, Translated};
+
+// Modules generated by `vmaot`, such as with
+// `cargo run --bin vmaot tests/fibo.bin > tests/translated/fibo.rs`
+#[path = "translated/fibo.rs"]
+mod fibo;
+#[path = "translated/hello_world.rs"]
+mod hello_world;
+#[path = "translated/patched.rs"]
+mod patched;
+#[path = "translated/rfact_native.rs"]
+mod rfact_native;
+
+// Run `program` and a machine loaded with its image, with `n` in r1 and
+// r10, checking that they end in the same state. The output of the
+// program is returned.
+fn compare(mut program: Translated, image: &[u8], n: u32) -> Vec<u8> {
+    let mut machine = Machine::new(image);
+    let (mut expected, mut out) = (Vec::new(), Vec::new());
+    for reg in [1, 10] {
+        machine.set_reg(reg, n).unwrap();
+        program.set_reg(reg, n).unwrap();
+    }
+    let result = machine.run_on(&mut expected).map_err(|e| e.to_string());
+    assert_eq!(result, program.run_on(&mut out).map_err(|e| e.to_string()));
+    assert_eq!(expected, out);
+    assert_eq!(machine.regs(), program.regs());
+    assert_eq!(machine.memory(), program.memory());
+    out
+}
+
+#[test]
+fn golden_modules() {
+    assert_eq!(
+        include_str!("translated/fibo.rs"),
+        translate(include_bytes!("fibo.bin"))
+    );
+    assert_eq!(
+        include_str!("translated/hello_world.rs"),
+        translate(include_bytes!("../examples/hello_world.bin"))
+    );
+    assert_eq!(
+        include_str!("translated/patched.rs"),
+        translate(include_bytes!("patched.bin"))
+    );
+    assert_eq!(
+        include_str!("translated/rfact_native.rs"),
+        translate(include_bytes!("rfact_native.bin"))
+    );
+}
+
+#[test]
+fn same_results() {
+    for n in 0..15 {
+        let program = fibo::new();
+        compare(program, fibo::IMAGE, n);
+    }
+    for n in 0..10 {
+        compare(rfact_native::new(), rfact_native::IMAGE, n);
+    }
+    let out = compare(hello_world::new(), hello_world::IMAGE, 0);
+    assert_eq!(b"Hello, world!\n", &out[..]);
+
+    // Everything ran as translated code
+    let mut program = fibo::new();
+    program.set_reg(10, 20).unwrap();
+    program.run_on(&mut Vec::new()).unwrap();
+    assert!(!program.interpreted());
+    assert_eq!(6765, program.regs()[11]);
+}
+
+#[test]
+fn self_modifying_code() {
+    let mut program = patched::new();
+    program.set_reg(1, 3).unwrap();
+    let mut out = Vec::new();
+    program.run_on(&mut out).unwrap();
+    assert_eq!(b"077", &out[..]);
+    assert!(program.interpreted());
+    compare(patched::new(), patched::IMAGE, 5);
+}
+
+#[test]
+fn faults() {
+    // The stack grows into the program, as reported by the interpreter
+    let mut program = rfact_native::new();
+    program.set_reg(10, 2000).unwrap();
+    let error = program.run_on(&mut Vec::new()).unwrap_err();
+    assert_eq!("stack overflow at 0021, with r2 at 44", error.to_string());
+    assert!(program.interpreted());
+    compare(rfact_native::new(), rfact_native::IMAGE, 2000);
+}
+
+#[test]
+fn call_targets() {
+    // 0: call #-1, 3: exit
+    // The block ends with the address to continue at, which the listing
+    // in the comments shows as signed
+    let module = translate(&[28, 0xff, 0xff, 7]);
+    let lines: Vec<&str> = module.lines().map(str::trim).collect();
+    assert!(lines.contains(&"4294967295"));
+    assert!(!lines.contains(&"-1"));
+}
@@ -0,0 +1,101 @@
+use interpreter::{
+    assemble, disassemble, Instruction, Machine, MachineError, CAUSE_INVALID_MEMORY, TRAP_CAUSE,
+    TRAP_IP, TRAP_VALUE, TRAP_VECTOR,
+};
+
+// Print the cause of every fault, and skip the 4-byte faulting instruction.
+const SKIPPING: &str = "\
+    loadimm r1 <- #handler
+    wrctl c0 <- r1
+    loadimm r5 <- #0
+    divu r6 <- r5, r5
+    out_number r6
+    exit
+handler:
+    rdctl r7 <- c2
+    out_number r7
+    rdctl r8 <- c1
+    loadimm r9 <- #4
+    add r8 <- r8, r9
+    wrctl c1 <- r8
+    rtt
+";
+
+#[test]
+fn handled_fault() {
+    let program = assemble(SKIPPING).unwrap();
+    let mut machine = Machine::new(&program.bytes);
+    let mut out = Vec::new();
+    machine.run_on(&mut out).unwrap();
+    assert_eq!(b"50", &out[..]);
+    assert_eq!(
+        &[program.label_address("handler").unwrap(), 15, 5, 0][..],
+        &machine.controls()[..4]
+    );
+}
+
+#[test]
+fn trap_details() {
+    // 0: load r1 <- [r2] with r2 == 5000, handled at 100
+    let mut machine = Machine::new(&[3, 1, 2]);
+    machine.set_reg(2, 5000).unwrap();
+    machine.set_control(TRAP_VECTOR, 100);
+    assert!(!machine.step_on(&mut Vec::new()).unwrap());
+    assert_eq!(100, machine.regs()[0]);
+    assert_eq!(0, machine.controls()[TRAP_IP as usize]);
+    assert_eq!(
+        CAUSE_INVALID_MEMORY,
+        machine.controls()[TRAP_CAUSE as usize]
+    );
+    assert_eq!(5000, machine.controls()[TRAP_VALUE as usize]);
+
+    // A fault in the handler is not trapped: 100 is not an instruction
+    assert!(matches!(
+        machine.step_on(&mut Vec::new()),
+        Err(MachineError::InvalidInstruction { ip: 100, opcode: 0 })
+    ));
+}
+
+#[test]
+fn without_handler() {
+    let program = assemble(SKIPPING).unwrap();
+    let mut machine = Machine::new(&program.bytes[7..]);
+    assert!(matches!(
+        machine.run_on(&mut Vec::new()),
+        Err(MachineError::DivisionByZero { ip: 4 })
+    ));
+}
+
+#[test]
+fn return_from_trap() {
+    // 0: divu r1 <- r1, r1, 4: exit, 5: handler: rtt
+    let mut machine = Machine::new(&[14, 1, 1, 1, 7, 35]);
+    machine.set_control(TRAP_VECTOR, 5);
+    for _ in 0..3 {
+        // The fault is retried after every return from the handler
+        assert!(!machine.step_on(&mut Vec::new()).unwrap());
+        assert_eq!(5, machine.regs()[0]);
+        assert!(!machine.step_on(&mut Vec::new()).unwrap());
+        assert_eq!(0, machine.regs()[0]);
+    }
+    machine.set_reg(1, 1).unwrap();
+    machine.run_on(&mut Vec::new()).unwrap();
+}
+
+#[test]
+fn control_instructions() {
+    assert_eq!(
+        vec![33, 1, 2, 34, 0, 3, 35],
+        assemble("rdctl r1 <- c2\nwrctl c0 <- r3\nrtt")
+            .unwrap()
+            .bytes
+    );
+    assert!(assemble("rdctl r1 <- c99").is_err());
+    assert!(matches!(
+        Instruction::decode(&[33, 1, 99]),
+        Err(MachineError::InvalidInstruction { opcode: 33, .. })
+    ));
+    let listing = disassemble(&assemble(SKIPPING).unwrap().bytes);
+    assert!(listing.contains("loc_0018:\n  0018   rdctl r7 <- c2"));
+    assert!(listing.contains("  0037   rtt"));
+}
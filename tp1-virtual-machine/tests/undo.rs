@@ -0,0 +1,95 @@
+use interpreter::{assemble, Machine, MachineError, TRAP_VECTOR};
+
+// Overwrite the pointer held in `table`, then use it much later.
+const CORRUPT: &str = "\
+    loadimm r1 <- #table
+    loadimm r3 <- #5000
+corrupt:
+    store [r1] <- r3
+    loadimm r4 <- #100
+    loadimm r5 <- #1
+loop:
+    sub r4 <- r4 - r5
+    bnz r4, #loop
+    load r6 <- [r1]
+    load r7 <- [r6]
+    exit
+table:
+    [40, 0, 0, 0]
+";
+
+#[test]
+fn find_corrupting_store() {
+    let program = assemble(CORRUPT).unwrap();
+    let table = program.label_address("table").unwrap() as usize;
+    let mut machine = Machine::new(&program.bytes);
+    machine.set_recording(true);
+    assert!(matches!(
+        machine.run_on(&mut Vec::new()),
+        Err(MachineError::InvalidMemoryAccess { address: 5000, .. })
+    ));
+    // The faulting load is recorded as well
+    assert_eq!(207, machine.recorded_steps());
+
+    // Walk back until the pointer gets its original value
+    while machine.memory()[table] != 40 {
+        assert!(machine.step_back());
+    }
+    assert_eq!(program.label_address("corrupt").unwrap(), machine.regs()[0]);
+    assert_eq!(2, machine.recorded_steps());
+}
+
+#[test]
+fn run_back_to() {
+    let program = assemble(CORRUPT).unwrap();
+    let start = Machine::new(&program.bytes).snapshot();
+    let mut machine = Machine::new(&program.bytes);
+    machine.set_recording(true);
+    assert!(machine.run_on(&mut Vec::new()).is_err());
+
+    let loop_address = program.label_address("loop").unwrap();
+    assert!(machine.run_back_to(loop_address));
+    assert_eq!(1, machine.regs()[4]);
+    assert!(machine.run_back_to(loop_address));
+    assert_eq!(2, machine.regs()[4]);
+    // The whole run is undone when the address is never reached
+    assert!(!machine.run_back_to(9999));
+    assert_eq!(0, machine.recorded_steps());
+    assert_eq!(start, machine.snapshot());
+}
+
+#[test]
+fn stack_and_traps() {
+    // 0: push r1, 2: call #7, 5: exit, 6: [0], 7: divu r1 <- r1, r3,
+    // with the trap handler at 100
+    let mut machine = Machine::new(&[26, 1, 28, 7, 0, 7, 0, 14, 1, 1, 3]);
+    machine.set_reg(1, 42).unwrap();
+    machine.set_reg(2, 4096).unwrap();
+    machine.set_control(TRAP_VECTOR, 100);
+    let start = machine.snapshot();
+    machine.set_recording(true);
+    for _ in 0..3 {
+        machine.step_on(&mut Vec::new()).unwrap();
+    }
+    assert_eq!(100, machine.regs()[0]);
+    assert_eq!(&[5, 0, 0, 0, 42, 0, 0, 0], &machine.memory()[4088..]);
+    while machine.step_back() {}
+    assert_eq!(start, machine.snapshot());
+}
+
+#[test]
+fn not_recording() {
+    let mut machine = Machine::new(&[7]);
+    machine.run_on(&mut Vec::new()).unwrap();
+    assert_eq!(0, machine.recorded_steps());
+    assert!(!machine.step_back());
+
+    // Stopping the recording forgets the recorded steps
+    machine.set_reg(0, 0).unwrap();
+    machine.set_recording(true);
+    machine.run_on(&mut Vec::new()).unwrap();
+    assert_eq!(1, machine.recorded_steps());
+    machine.set_recording(false);
+    machine.set_recording(true);
+    assert!(!machine.step_back());
+}